@@ -1,5 +1,6 @@
 use bulletproofs::PedersenGens;
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use zk_ballot_validation::ballot_validation::election_context::ElectionContext;
 use zk_ballot_validation::ballot_validation::rated_voting::no_budget;
 use zk_ballot_validation::ballot_validation::rated_voting::max_budget;
 use zk_ballot_validation::ballot_validation::ranked_voting;
@@ -10,6 +11,7 @@ fn benchmark_proof_generation(c: &mut Criterion) {
     let pc_gens = PedersenGens::default();
     let range = (-10, 10);
     let max_credits = 1;
+    let context = ElectionContext::new(b"benchmark election", b"benchmark contest", b"benchmark voter");
 
     for &ballot_size in &[4, 8, 16, 32, 64, 128, 256] {
         let bp_params_no_budget = no_budget::setup(range, ballot_size, Some(pc_gens)).expect("Failed to set up no-budget voting parameters");
@@ -21,15 +23,16 @@ fn benchmark_proof_generation(c: &mut Criterion) {
             |b, &_size| {
                 b.iter(|| {
                     let proof = no_budget::generate_vote(
-                        black_box(ballot_no_budget.clone()),
-                        &bp_params_no_budget
+                        &bp_params_no_budget,
+                        &context,
+                        black_box(&ballot_no_budget),
                     );
                     let _ = black_box(proof);
                 });
             },
         );
 
-        let bp_params_max_budget = max_budget::setup(max_credits, ballot_size, Some(pc_gens)).expect("Failed to set up max-budget voting parameters");
+        let bp_params_max_budget = max_budget::setup(max_credits, None, None, ballot_size, Some(pc_gens)).expect("Failed to set up max-budget voting parameters");
         let mut ballot_max_budget = vec![0; ballot_size];
         ballot_max_budget[0] = 1;
 
@@ -40,7 +43,8 @@ fn benchmark_proof_generation(c: &mut Criterion) {
                 b.iter(|| {
                     let proof = max_budget::generate_vote(
                         &bp_params_max_budget,
-                        black_box(ballot_max_budget.clone()),
+                        &context,
+                        black_box(&ballot_max_budget),
                     );
                     let _ = black_box(proof);
                 });
@@ -49,7 +53,7 @@ fn benchmark_proof_generation(c: &mut Criterion) {
 
         let vec_a: Vec<u32> = (0..ballot_size).map(|x| x as u32).collect();
         let vec_a_permuted: Vec<u32> = vec_a.iter().cloned().rev().collect();
-        let setup_params = ranked_voting::setup(ballot_size);
+        let setup_params = ranked_voting::setup(ballot_size, b"benchmark election").expect("Failed to set up ranked voting parameters");
 
         group.bench_with_input(
             BenchmarkId::new("Ranked Voting", ballot_size),
@@ -57,8 +61,9 @@ fn benchmark_proof_generation(c: &mut Criterion) {
             |b, &_size| {
                 b.iter(|| {
                     let proof = ranked_voting::generate_vote(
-                        black_box(&vec_a_permuted),
                         &setup_params,
+                        &context,
+                        black_box(&vec_a_permuted),
                     );
                     let _ = black_box(proof);
                 });
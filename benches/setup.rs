@@ -24,7 +24,7 @@ fn benchmark_setup(c: &mut Criterion) {
             &ballot_size,
             |b, &_size| {
                 b.iter(|| {
-                    let setup_params = rated_voting::max_budget::setup(max_credits, ballot_size, None).expect("Failed to set up max-budget voting parameters");
+                    let setup_params = rated_voting::max_budget::setup(max_credits, None, None, ballot_size, None).expect("Failed to set up max-budget voting parameters");
                     let _ = black_box(setup_params);
                 });
             },
@@ -35,7 +35,7 @@ fn benchmark_setup(c: &mut Criterion) {
             &ballot_size,
             |b, &_size| {
                 b.iter(|| {
-                    let setup_params = ranked_voting::setup(ballot_size);
+                    let setup_params = ranked_voting::setup(ballot_size, b"benchmark election").expect("Failed to set up ranked voting parameters");
                     let _ = black_box(setup_params);
                 });
             },
@@ -1,6 +1,7 @@
 #![no_main]
 
 use zk_ballot_validation::ballot_validation::rated_voting::max_budget::{setup, generate_vote};
+use zk_ballot_validation::ballot_validation::election_context::ElectionContext;
 use libfuzzer_sys::fuzz_target;
 use arbitrary::Arbitrary;
 
@@ -11,6 +12,7 @@ struct MaxBudgetInput {
 fuzz_target!(|input: MaxBudgetInput| {
     let ballot = input.ballot.to_vec();
 
-    let setup_params = setup(100, ballot.len(), None);
-    let _ = generate_vote(&setup_params, ballot);
+    let setup_params = setup(100, None, None, ballot.len(), None).expect("Failed to set up max-budget voting parameters");
+    let context = ElectionContext::new(b"fuzz election", b"fuzz contest", b"fuzz voter");
+    let _ = generate_vote(&setup_params, &context, &ballot);
 });
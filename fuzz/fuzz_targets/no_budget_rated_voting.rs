@@ -1,6 +1,7 @@
 #![no_main]
 
 use zk_ballot_validation::ballot_validation::rated_voting::no_budget::{setup, generate_vote};
+use zk_ballot_validation::ballot_validation::election_context::ElectionContext;
 use libfuzzer_sys::fuzz_target;
 use arbitrary::Arbitrary;
 
@@ -20,5 +21,6 @@ fuzz_target!(|input: NoBudgetInput| {
         }
     };
 
-    let _ = generate_vote(ballot, &bp_params);
+    let context = ElectionContext::new(b"fuzz election", b"fuzz contest", b"fuzz voter");
+    let _ = generate_vote(&bp_params, &context, &ballot);
 });
\ No newline at end of file
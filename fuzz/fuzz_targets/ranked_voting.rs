@@ -1,6 +1,7 @@
 #![no_main]
 
 use zk_ballot_validation::ballot_validation::ranked_voting::{setup, generate_vote};
+use zk_ballot_validation::ballot_validation::election_context::ElectionContext;
 use libfuzzer_sys::fuzz_target;
 use arbitrary::Arbitrary;
 
@@ -10,8 +11,8 @@ struct RankedVotingInput {
 }
 fuzz_target!(|input: RankedVotingInput| {
     let ballot = input.ballot.to_vec();
-    let vec_a = vec![1,2,3,4,5,6,7,8,9,10];
 
-    let setup_params = setup(vec_a.len(), vec_a);
-    let proof = generate_vote(&ballot, &setup_params);
+    let setup_params = setup(ballot.len(), b"fuzz election").expect("Failed to set up ranked voting parameters");
+    let context = ElectionContext::new(b"fuzz election", b"fuzz contest", b"fuzz voter");
+    let _ = generate_vote(&setup_params, &context, &ballot);
 });
\ No newline at end of file
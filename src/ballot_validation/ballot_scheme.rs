@@ -0,0 +1,233 @@
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+use crate::ballot_validation::election_context::ElectionContext;
+use crate::ballot_validation::errors::{BatchVerificationError, VerificationError};
+
+/// Common interface over the ballot validation protocols.
+///
+/// Each scheme has its own `setup` function, since the election parameters
+/// differ between protocols, but once the parameters exist every scheme
+/// generates and verifies ballots the same way.
+pub trait BallotScheme {
+    type Params;
+    type Ballot: ?Sized;
+    type Proof;
+    type Error;
+
+    /// Generates a ballot proof, drawing every blinding factor from OS entropy.
+    fn generate_vote(
+        params: &Self::Params,
+        context: &ElectionContext,
+        ballot: &Self::Ballot,
+    ) -> Result<Self::Proof, Self::Error> {
+        Self::generate_vote_with_rng(params, context, ballot, &mut OsRng)
+    }
+
+    fn generate_vote_with_rng<R: RngCore + CryptoRng>(
+        params: &Self::Params,
+        context: &ElectionContext,
+        ballot: &Self::Ballot,
+        rng: &mut R,
+    ) -> Result<Self::Proof, Self::Error>;
+
+    fn verify_proof(params: &Self::Params, context: &ElectionContext, proof: &Self::Proof) -> Result<(), VerificationError>;
+
+    /// Verifies many ballots cast under the same parameters and reports every
+    /// invalid one. Checks them one by one unless the scheme can share work
+    /// across ballots. Bulletproofs 4.0 verifies range and R1CS proofs one at
+    /// a time, so only the sigma proofs around them can be batched.
+    fn verify_batch(
+        params: &Self::Params,
+        ballots: &[(&ElectionContext, &Self::Proof)],
+    ) -> Result<(), BatchVerificationError> {
+        verify_each(ballots, |context, proof| Self::verify_proof(params, context, proof))
+    }
+}
+
+/// Verifies the ballots individually, collecting the position and error of
+/// every one that fails.
+pub(crate) fn verify_each<P>(
+    ballots: &[(&ElectionContext, &P)],
+    verify: impl Fn(&ElectionContext, &P) -> Result<(), VerificationError>,
+) -> Result<(), BatchVerificationError> {
+    let invalid: Vec<(usize, VerificationError)> = ballots
+        .iter()
+        .enumerate()
+        .filter_map(|(index, (context, proof))| verify(context, proof).err().map(|e| (index, e)))
+        .collect();
+
+    if !invalid.is_empty() {
+        return Err(BatchVerificationError { invalid });
+    }
+
+    Ok(())
+}
+
+/// Runs the per-ballot checks with `defer`, which queues the rest of each
+/// ballot's checks into the shared `batch`, then checks the batch once. If the
+/// batch fails, the ballots not already rejected are verified individually to
+/// find the invalid ones.
+pub(crate) fn verify_batched<P, B>(
+    ballots: &[(&ElectionContext, &P)],
+    mut batch: B,
+    defer: impl Fn(&mut B, &ElectionContext, &P) -> Result<(), VerificationError>,
+    batch_holds: impl FnOnce(B) -> bool,
+    verify: impl Fn(&ElectionContext, &P) -> Result<(), VerificationError>,
+) -> Result<(), BatchVerificationError> {
+    let mut errors: Vec<Option<VerificationError>> = ballots
+        .iter()
+        .map(|(context, proof)| defer(&mut batch, context, proof).err())
+        .collect();
+
+    if !batch_holds(batch) {
+        for ((context, proof), error) in ballots.iter().zip(errors.iter_mut()) {
+            if error.is_none() {
+                *error = verify(context, proof).err();
+            }
+        }
+    }
+
+    let invalid: Vec<(usize, VerificationError)> = errors
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, error)| error.map(|e| (index, e)))
+        .collect();
+
+    if !invalid.is_empty() {
+        return Err(BatchVerificationError { invalid });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{de::DeserializeOwned, Serialize};
+    use crate::ballot_validation::election_context::test_context;
+    use crate::ballot_validation::plurality::{self, PluralityVoting};
+    use crate::ballot_validation::ranked_voting::{self, RankedVoting};
+    #[cfg(feature = "r1cs")]
+    use crate::ballot_validation::ranked_voting::weak_ordering::{self, WeakOrderingRankedVoting};
+    use crate::ballot_validation::rated_voting::approval::{self, ApprovalVoting};
+    use crate::ballot_validation::rated_voting::exact_budget::{self, ExactBudgetRatedVoting};
+    use crate::ballot_validation::rated_voting::max_budget::{self, MaxBudgetRatedVoting};
+    use crate::ballot_validation::rated_voting::multi_winner::{self, MultiWinnerVoting};
+    use crate::ballot_validation::rated_voting::no_budget::{self, NoBudgetRatedVoting};
+    #[cfg(feature = "r1cs")]
+    use crate::ballot_validation::rated_voting::quadratic::{self, QuadraticRatedVoting};
+
+    fn cast_and_verify<S: BallotScheme>(params: &S::Params, ballot: &S::Ballot) -> bool {
+        let context = test_context();
+        match S::generate_vote(params, &context, ballot) {
+            Ok(proof) => S::verify_proof(params, &context, &proof).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    /// Round-trips the parameters through serde, which carries the
+    /// `to_bytes` encoding, and checks that a proof verifies under the decoded
+    /// parameters while truncated or mistagged encodings are rejected.
+    fn setup_round_trips<S: BallotScheme>(params: &S::Params, ballot: &S::Ballot) -> bool
+    where
+        S::Params: Serialize + DeserializeOwned,
+        S::Error: core::fmt::Debug,
+    {
+        let bytes = bincode::serialize(params).unwrap();
+        let decoded: S::Params = bincode::deserialize(&bytes).unwrap();
+        let proof = S::generate_vote(params, &test_context(), ballot).unwrap();
+
+        S::verify_proof(&decoded, &test_context(), &proof).is_ok()
+            && bincode::serialize(&decoded).unwrap() == bytes
+            && bincode::deserialize::<S::Params>(&truncated(&bytes)).is_err()
+    }
+
+    /// Like [`setup_round_trips`], and also round-trips the proof.
+    fn proof_round_trips<S: BallotScheme>(params: &S::Params, ballot: &S::Ballot) -> bool
+    where
+        S::Params: Serialize + DeserializeOwned,
+        S::Proof: Serialize + DeserializeOwned,
+        S::Error: core::fmt::Debug,
+    {
+        let proof = S::generate_vote(params, &test_context(), ballot).unwrap();
+        let bytes = bincode::serialize(&proof).unwrap();
+        let decoded: S::Proof = bincode::deserialize(&bytes).unwrap();
+        let params_bytes = bincode::serialize(params).unwrap();
+
+        setup_round_trips::<S>(params, ballot)
+            && S::verify_proof(params, &test_context(), &decoded).is_ok()
+            && bincode::serialize(&decoded).unwrap() == bytes
+            && bincode::deserialize::<S::Proof>(&truncated(&bytes)).is_err()
+            && bincode::deserialize::<S::Proof>(&params_bytes).is_err()
+            && bincode::deserialize::<S::Params>(&bytes).is_err()
+    }
+
+    /// bincode writes the encoding behind its length, so this drops the last
+    /// byte of the encoding itself rather than of the bincode framing.
+    fn truncated(bytes: &[u8]) -> Vec<u8> {
+        bincode::serialize(&bytes[8..bytes.len() - 1]).unwrap()
+    }
+
+    #[test]
+    fn test_schemes_are_interchangeable() {
+        let ranked_params = ranked_voting::setup(4, b"interchangeable schemes").unwrap();
+        assert!(cast_and_verify::<RankedVoting>(&ranked_params, &[3, 1, 0, 2]));
+
+        #[cfg(feature = "r1cs")]
+        {
+            let weak_ordering_params = weak_ordering::setup(4, None).unwrap();
+            assert!(cast_and_verify::<WeakOrderingRankedVoting>(&weak_ordering_params, &[3, 1, 1, 0]));
+            assert!(!cast_and_verify::<WeakOrderingRankedVoting>(&weak_ordering_params, &[3, 2, 2, 0]));
+        }
+
+        let no_budget_params = no_budget::setup((-5, 5), 4, None).unwrap();
+        assert!(cast_and_verify::<NoBudgetRatedVoting>(&no_budget_params, &[-5, 0, 2, 5]));
+
+        let max_budget_params = max_budget::setup(10, None, None, 4, None).unwrap();
+        assert!(cast_and_verify::<MaxBudgetRatedVoting>(&max_budget_params, &[4, 0, 3, 3]));
+        assert!(!cast_and_verify::<MaxBudgetRatedVoting>(&max_budget_params, &[4, 4, 3, 3]));
+
+        let exact_budget_params = exact_budget::setup(10, None, 4, None).unwrap();
+        assert!(cast_and_verify::<ExactBudgetRatedVoting>(&exact_budget_params, &[4, 0, 3, 3]));
+        assert!(!cast_and_verify::<ExactBudgetRatedVoting>(&exact_budget_params, &[4, 0, 3, 2]));
+
+        #[cfg(feature = "r1cs")]
+        {
+            let quadratic_params = quadratic::setup(10, 4, None).unwrap();
+            assert!(cast_and_verify::<QuadraticRatedVoting>(&quadratic_params, &[-2, 0, 2, 1]));
+            assert!(!cast_and_verify::<QuadraticRatedVoting>(&quadratic_params, &[-2, 2, 2, 0]));
+        }
+
+        let approval_params = approval::setup(4, None, Some(2), None).unwrap();
+        assert!(cast_and_verify::<ApprovalVoting>(&approval_params, &[true, false, true, false]));
+        assert!(!cast_and_verify::<ApprovalVoting>(&approval_params, &[true, true, true, false]));
+
+        let plurality_params = plurality::setup(4, false, None).unwrap();
+        assert!(cast_and_verify::<PluralityVoting>(&plurality_params, &[false, false, true, false]));
+        assert!(!cast_and_verify::<PluralityVoting>(&plurality_params, &[false; 4]));
+
+        let multi_winner_params = multi_winner::setup(4, 1, 2, None).unwrap();
+        assert!(cast_and_verify::<MultiWinnerVoting>(&multi_winner_params, &[true, false, true, false]));
+        assert!(!cast_and_verify::<MultiWinnerVoting>(&multi_winner_params, &[true, true, true, false]));
+    }
+
+    #[test]
+    fn test_encodings_round_trip() {
+        let ranked_params = ranked_voting::setup_truncated(8, 5, b"round trip").unwrap();
+        assert!(setup_round_trips::<RankedVoting>(&ranked_params, &[0, 4, 0, 3, 2, 0, 1, 0]));
+        #[cfg(feature = "r1cs")]
+        assert!(proof_round_trips::<WeakOrderingRankedVoting>(&weak_ordering::setup(3, None).unwrap(), &[2, 0, 0]));
+        assert!(proof_round_trips::<NoBudgetRatedVoting>(&no_budget::setup((-10, 10), 4, None).unwrap(), &[1, -3, 0, 10]));
+        assert!(proof_round_trips::<MaxBudgetRatedVoting>(&max_budget::setup(20, None, None, 4, None).unwrap(), &[5, 3, 6, 6]));
+        assert!(proof_round_trips::<MaxBudgetRatedVoting>(&max_budget::setup(300, Some(100), Some(200), 2, None).unwrap(), &[200, 50]));
+        assert!(proof_round_trips::<ExactBudgetRatedVoting>(&exact_budget::setup(300, Some(200), 4, None).unwrap(), &[200, 50, 50, 0]));
+        #[cfg(feature = "r1cs")]
+        assert!(proof_round_trips::<QuadraticRatedVoting>(&quadratic::setup(1_000_000, 3, None).unwrap(), &[-1000, 0, 0]));
+        for (min, max) in [(None, None), (Some(1), Some(3))] {
+            assert!(proof_round_trips::<ApprovalVoting>(&approval::setup(5, min, max, None).unwrap(), &[true, false, true, false, false]));
+        }
+        for allow_abstain in [false, true] {
+            assert!(proof_round_trips::<PluralityVoting>(&plurality::setup(4, allow_abstain, None).unwrap(), &[false, true, false, false]));
+        }
+        assert!(proof_round_trips::<MultiWinnerVoting>(&multi_winner::setup(3, 1, 2, None).unwrap(), &[false, true, true]));
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use curve25519_dalek_ng::{ristretto::RistrettoPoint, scalar::Scalar};
+use bulletproofs::PedersenGens;
+use crate::ballot_validation::encoding::{ObjectKind, Reader, SchemeTag, Writer};
+use crate::ballot_validation::errors::{BallotError, DecodingError, VerificationError};
+use crate::ballot_validation::tally::{scalar_from_i128, TallyScheme};
+
+/// Largest number of baby steps a table may hold, which keeps it to a few GiB
+/// and bounds decodable ranges to 2^52 values.
+const MAX_BABY_STEPS: u128 = 1 << 26;
+
+/// Baby-step giant-step table for recovering `v` from `v * B` with `v` in
+/// `[min, max]`. Building it costs about `sqrt(max - min)` point additions,
+/// so it is meant to be built once per election and cached with `to_bytes`.
+pub struct DiscreteLogTable {
+    base: RistrettoPoint,
+    min: i64,
+    max: i64,
+    baby_steps: HashMap<[u8; 32], u64>,
+}
+
+impl DiscreteLogTable {
+    pub fn new(pc_gens: &PedersenGens, min: i64, max: i64) -> Result<DiscreteLogTable, BallotError> {
+        let step_count: u64 = step_count(min, max)?;
+
+        let mut baby_steps: HashMap<[u8; 32], u64> = HashMap::with_capacity(step_count as usize);
+        let mut point = RistrettoPoint::default();
+        for j in 0..step_count {
+            baby_steps.insert(point.compress().to_bytes(), j);
+            point += pc_gens.B;
+        }
+
+        Ok(DiscreteLogTable { base: pc_gens.B, min, max, baby_steps })
+    }
+
+    /// Table covering every per-candidate total `voters` ballots of scheme
+    /// `S` can add up to.
+    pub fn for_tally<S: TallyScheme>(params: &S::Params, voters: u64) -> Result<DiscreteLogTable, BallotError> {
+        let (pc_gens, _, _) = S::tally_setup(params);
+        let (min, max) = S::vote_bounds(params);
+        let total = |bound: i128| -> Result<i64, BallotError> {
+            bound
+                .checked_mul(voters as i128)
+                .and_then(|total| i64::try_from(total).ok())
+                .ok_or(BallotError::Overflow)
+        };
+
+        DiscreteLogTable::new(&pc_gens, total(min)?, total(max)?)
+    }
+
+    pub fn range(&self) -> (i64, i64) {
+        (self.min, self.max)
+    }
+
+    /// Returns `v` if `point = v * B` for some `v` in the table's range.
+    pub fn solve(&self, point: &RistrettoPoint) -> Option<i64> {
+        let values: u128 = (self.max as i128 - self.min as i128) as u128 + 1;
+        let step_count: u64 = self.baby_steps.len() as u64;
+        let giant_step: RistrettoPoint = Scalar::from(step_count) * self.base;
+
+        let mut current: RistrettoPoint = point - scalar_from_i128(self.min as i128) * self.base;
+        for i in 0..values.div_ceil(step_count as u128) {
+            if let Some(&j) = self.baby_steps.get(current.compress().as_bytes()) {
+                let offset: u128 = i * step_count as u128 + j as u128;
+                let value = (self.min as i128 + offset as i128) as i64;
+                // Guards against tables whose cached bytes have been tampered with.
+                if offset < values && scalar_from_i128(value as i128) * self.base == *point {
+                    return Some(value);
+                }
+            }
+            current -= giant_step;
+        }
+
+        None
+    }
+
+    /// Decodes decrypted per-candidate totals.
+    pub fn decode_totals(&self, points: &[RistrettoPoint]) -> Result<Vec<i64>, VerificationError> {
+        points
+            .iter()
+            .enumerate()
+            .map(|(candidate, point)| self.solve(point).ok_or(VerificationError::TotalOutOfRange { candidate }))
+            .collect()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut ordered: Vec<(&[u8; 32], &u64)> = self.baby_steps.iter().collect();
+        ordered.sort_unstable_by_key(|(_, &j)| j);
+        let steps: Vec<u8> = ordered.into_iter().flat_map(|(key, _)| key.iter().copied()).collect();
+
+        let mut writer = Writer::new(SchemeTag::Tally, ObjectKind::DiscreteLogTable);
+        writer
+            .point(&self.base.compress())
+            .i64(self.min)
+            .i64(self.max)
+            .bytes(&steps);
+        writer.into_bytes()
+    }
+
+    /// Rebuilds a cached table without recomputing the baby steps. The table
+    /// must have been built for `pc_gens`, since `solve` relies on its base to
+    /// catch tampered baby steps.
+    pub fn from_bytes(bytes: &[u8], pc_gens: &PedersenGens) -> Result<DiscreteLogTable, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::Tally, ObjectKind::DiscreteLogTable)?;
+        let base = reader.decompressed_point()?;
+        let (min, max) = (reader.i64()?, reader.i64()?);
+        let steps = reader.bytes()?;
+        reader.finish()?;
+
+        if base != pc_gens.B {
+            return Err(DecodingError::InvalidParameters(BallotError::InvalidSetup("table base does not match the Pedersen generators".into())));
+        }
+
+        let step_count: u64 = step_count(min, max).map_err(DecodingError::InvalidParameters)?;
+        if steps.len() as u128 != step_count as u128 * 32 {
+            return Err(DecodingError::InvalidLength);
+        }
+
+        let baby_steps: HashMap<[u8; 32], u64> = steps
+            .chunks_exact(32)
+            .zip(0..)
+            .map(|(key, j)| (key.try_into().unwrap(), j))
+            .collect();
+        if baby_steps.len() as u64 != step_count {
+            return Err(DecodingError::InvalidPoint);
+        }
+
+        Ok(DiscreteLogTable { base, min, max, baby_steps })
+    }
+}
+
+/// `ceil(sqrt(max - min + 1))`, the number of baby steps for the range.
+fn step_count(min: i64, max: i64) -> Result<u64, BallotError> {
+    if min > max {
+        return Err(BallotError::InvalidSetup("discrete log range must have min <= max".into()));
+    }
+
+    let values: u128 = (max as i128 - min as i128) as u128 + 1;
+    let mut steps: u128 = (values as f64).sqrt() as u128;
+    while steps * steps < values {
+        steps += 1;
+    }
+    if steps > MAX_BABY_STEPS {
+        return Err(BallotError::InvalidSetup("discrete log range is too large".into()));
+    }
+
+    Ok(steps as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+    use crate::ballot_validation::election_context::test_context;
+    use crate::ballot_validation::elgamal::SecretKey;
+    use crate::ballot_validation::encrypted_ballot::{generate_encrypted_vote, EncryptedTally};
+    use crate::ballot_validation::rated_voting::max_budget::{self, MaxBudgetRatedVoting};
+    use crate::ballot_validation::rated_voting::no_budget::{self, NoBudgetRatedVoting};
+
+    fn encode(pc_gens: &PedersenGens, value: i64) -> RistrettoPoint {
+        scalar_from_i128(value as i128) * pc_gens.B
+    }
+
+    #[test]
+    fn test_solves_every_value_in_range() {
+        let pc_gens = PedersenGens::default();
+        let table = DiscreteLogTable::new(&pc_gens, -20, 30).unwrap();
+
+        for value in -20..=30 {
+            assert_eq!(table.solve(&encode(&pc_gens, value)), Some(value));
+        }
+        assert_eq!(table.solve(&encode(&pc_gens, -21)), None);
+        assert_eq!(table.solve(&encode(&pc_gens, 31)), None);
+    }
+
+    #[test]
+    fn test_million_voter_tally() {
+        let pc_gens = PedersenGens::default();
+        let params = max_budget::setup(100, None, Some(10), 4, None).unwrap();
+        let table = DiscreteLogTable::for_tally::<MaxBudgetRatedVoting>(&params, 1_000_000).unwrap();
+
+        assert_eq!(table.range(), (0, 10_000_000));
+        let totals = [0, 9_876_543, 10_000_000];
+        let points: Vec<RistrettoPoint> = totals.iter().map(|&total| encode(&pc_gens, total)).collect();
+        assert_eq!(table.decode_totals(&points).unwrap(), totals);
+        assert_eq!(table.decode_totals(&[encode(&pc_gens, 10_000_001)]), Err(VerificationError::TotalOutOfRange { candidate: 0 }));
+    }
+
+    #[test]
+    fn test_decodes_encrypted_tally() {
+        let pc_gens = PedersenGens::default();
+        let params = no_budget::setup((-5, 5), 2, None).unwrap();
+        let secret_key = SecretKey::generate(&mut thread_rng());
+        let public_key = secret_key.public_key(&pc_gens);
+
+        let mut tally = EncryptedTally::new(2);
+        for ballot in [[-5, 4], [-2, 5], [3, -1]] {
+            let ballot = generate_encrypted_vote::<NoBudgetRatedVoting>(&params, &public_key, &test_context(), &ballot).unwrap();
+            tally.add(&params, &public_key, &test_context(), &ballot).unwrap();
+        }
+
+        let table = DiscreteLogTable::for_tally::<NoBudgetRatedVoting>(&params, tally.ballot_count()).unwrap();
+        assert_eq!(table.range(), (-15, 15));
+        let decrypted: Vec<RistrettoPoint> = tally.sums().iter().map(|c| secret_key.decrypt(c)).collect();
+        assert_eq!(table.decode_totals(&decrypted).unwrap(), vec![-4, 8]);
+    }
+
+    #[test]
+    fn test_cached_table_round_trip() {
+        let pc_gens = PedersenGens::default();
+        let table = DiscreteLogTable::new(&pc_gens, -1000, 1000).unwrap();
+
+        let decoded = DiscreteLogTable::from_bytes(&table.to_bytes(), &pc_gens).unwrap();
+        assert_eq!(decoded.solve(&encode(&pc_gens, -999)), Some(-999));
+
+        // A corrupted cache may fail to decode a value, but never decodes it wrongly.
+        let mut bytes = table.to_bytes();
+        let len = bytes.len();
+        bytes[len - 32..].copy_from_slice(encode(&pc_gens, 5000).compress().as_bytes());
+        let corrupted = DiscreteLogTable::from_bytes(&bytes, &pc_gens).unwrap();
+        for value in [-956, 0, 1000] {
+            assert!(corrupted.solve(&encode(&pc_gens, value)).is_none_or(|solved| solved == value));
+        }
+
+        bytes.truncate(len - 1);
+        assert!(DiscreteLogTable::from_bytes(&bytes, &pc_gens).is_err());
+    }
+
+    #[test]
+    fn test_cached_table_with_other_base_is_rejected() {
+        let pc_gens = PedersenGens::default();
+        let other = PedersenGens { B: pc_gens.B_blinding, B_blinding: pc_gens.B };
+        let table = DiscreteLogTable::new(&other, 0, 100).unwrap();
+
+        assert!(matches!(
+            DiscreteLogTable::from_bytes(&table.to_bytes(), &pc_gens),
+            Err(DecodingError::InvalidParameters(BallotError::InvalidSetup(_)))
+        ));
+        assert!(DiscreteLogTable::from_bytes(&table.to_bytes(), &other).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_ranges() {
+        let pc_gens = PedersenGens::default();
+        assert!(DiscreteLogTable::new(&pc_gens, 5, 4).is_err());
+        assert!(DiscreteLogTable::new(&pc_gens, i64::MIN, i64::MAX).is_err());
+        assert_eq!(DiscreteLogTable::new(&pc_gens, 7, 7).unwrap().solve(&encode(&pc_gens, 7)), Some(7));
+
+        let params = max_budget::setup(u64::MAX >> 1, None, None, 2, None).unwrap();
+        assert_eq!(DiscreteLogTable::for_tally::<MaxBudgetRatedVoting>(&params, 4).err(), Some(BallotError::Overflow));
+    }
+}
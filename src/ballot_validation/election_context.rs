@@ -0,0 +1,107 @@
+use merlin::Transcript;
+
+/// Identifies the contest a ballot is cast in. Every proof transcript starts
+/// by absorbing the context, so a proof only verifies under the context it was
+/// generated for and cannot be replayed in another election, contest or
+/// voting session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ElectionContext {
+    election_id: Vec<u8>,
+    contest_id: Vec<u8>,
+    session_id: Vec<u8>,
+}
+
+impl ElectionContext {
+    pub fn new(election_id: &[u8], contest_id: &[u8], session_id: &[u8]) -> ElectionContext {
+        ElectionContext {
+            election_id: election_id.to_vec(),
+            contest_id: contest_id.to_vec(),
+            session_id: session_id.to_vec(),
+        }
+    }
+
+    pub fn election_id(&self) -> &[u8] {
+        &self.election_id
+    }
+
+    pub fn contest_id(&self) -> &[u8] {
+        &self.contest_id
+    }
+
+    pub fn session_id(&self) -> &[u8] {
+        &self.session_id
+    }
+
+    /// Starts a transcript for the protocol `label`, bound to this context.
+    pub(crate) fn transcript(&self, label: &'static [u8]) -> Transcript {
+        let mut transcript = Transcript::new(label);
+        transcript.append_message(b"election id", &self.election_id);
+        transcript.append_message(b"contest id", &self.contest_id);
+        transcript.append_message(b"session id", &self.session_id);
+        transcript
+    }
+
+    /// Starts a ballot proof transcript that also absorbs the encoded setup
+    /// parameters, so the proof only verifies under the exact parameters it
+    /// was generated with.
+    pub(crate) fn scheme_transcript(&self, label: &'static [u8], parameters: &[u8]) -> Transcript {
+        let mut transcript = self.transcript(label);
+        transcript.append_message(b"setup parameters", parameters);
+        transcript
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn test_context() -> ElectionContext {
+    ElectionContext::new(b"test election", b"test contest", b"test voter")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenge(context: &ElectionContext) -> [u8; 32] {
+        challenge_of(context.transcript(b"test"))
+    }
+
+    fn challenge_of(mut transcript: Transcript) -> [u8; 32] {
+        let mut challenge = [0u8; 32];
+        transcript.challenge_bytes(b"challenge", &mut challenge);
+        challenge
+    }
+
+    #[test]
+    fn test_every_field_changes_the_transcript() {
+        let context = test_context();
+        let variants = [
+            ElectionContext::new(b"other election", b"test contest", b"test voter"),
+            ElectionContext::new(b"test election", b"other contest", b"test voter"),
+            ElectionContext::new(b"test election", b"test contest", b"other voter"),
+        ];
+
+        for variant in &variants {
+            assert_ne!(challenge(&context), challenge(variant));
+        }
+        assert_eq!(challenge(&context), challenge(&test_context()));
+    }
+
+    #[test]
+    fn test_fields_are_not_concatenated() {
+        let split = ElectionContext::new(b"ab", b"c", b"");
+        let shifted = ElectionContext::new(b"a", b"bc", b"");
+        assert_ne!(challenge(&split), challenge(&shifted));
+    }
+
+    #[test]
+    fn test_scheme_transcript_binds_parameters() {
+        let context = ElectionContext::new(b"election", b"contest", b"voter");
+        let bound = challenge_of(context.scheme_transcript(b"test", b"parameters"));
+
+        assert_eq!(bound, challenge_of(context.scheme_transcript(b"test", b"parameters")));
+        assert_ne!(bound, challenge_of(context.scheme_transcript(b"test", b"other parameters")));
+        assert_ne!(bound, challenge(&context));
+        assert_eq!(context.election_id(), b"election");
+        assert_eq!(context.contest_id(), b"contest");
+        assert_eq!(context.session_id(), b"voter");
+    }
+}
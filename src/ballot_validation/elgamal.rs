@@ -0,0 +1,244 @@
+use core::ops::{Add, AddAssign};
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+use curve25519_dalek_ng::{ristretto::{CompressedRistretto, RistrettoPoint}, scalar::Scalar, traits::Identity};
+use bulletproofs::PedersenGens;
+use zeroize::Zeroize;
+
+/// Election public key for exponential ElGamal. Keys live on the value
+/// generator `B` of the Pedersen generators the ballots are committed with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PublicKey {
+    point: RistrettoPoint,
+}
+
+/// Election secret key, zeroed on drop.
+pub struct SecretKey {
+    scalar: Scalar,
+}
+
+/// Exponential ElGamal ciphertext `(k * B, v * B + k * pk)`. Ciphertexts
+/// under the same key add up to an encryption of the sum of their values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ciphertext {
+    c1: RistrettoPoint,
+    c2: RistrettoPoint,
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.scalar.zeroize();
+    }
+}
+
+impl PublicKey {
+    pub fn from_point(point: RistrettoPoint) -> PublicKey {
+        PublicKey { point }
+    }
+
+    pub fn point(&self) -> &RistrettoPoint {
+        &self.point
+    }
+
+    pub fn encrypt(&self, pc_gens: &PedersenGens, value: &Scalar, randomness: &Scalar) -> Ciphertext {
+        Ciphertext {
+            c1: randomness * pc_gens.B,
+            c2: value * pc_gens.B + randomness * self.point,
+        }
+    }
+}
+
+impl SecretKey {
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> SecretKey {
+        SecretKey { scalar: Scalar::random(rng) }
+    }
+
+    pub fn from_scalar(scalar: Scalar) -> SecretKey {
+        SecretKey { scalar }
+    }
+
+    pub fn public_key(&self, pc_gens: &PedersenGens) -> PublicKey {
+        PublicKey { point: self.scalar * pc_gens.B }
+    }
+
+    /// Recovers `v * B`; finding `v` itself is a bounded discrete log.
+    pub fn decrypt(&self, ciphertext: &Ciphertext) -> RistrettoPoint {
+        ciphertext.c2 - self.scalar * ciphertext.c1
+    }
+}
+
+impl Ciphertext {
+    pub fn new(c1: RistrettoPoint, c2: RistrettoPoint) -> Ciphertext {
+        Ciphertext { c1, c2 }
+    }
+
+    pub fn zero() -> Ciphertext {
+        Ciphertext { c1: RistrettoPoint::identity(), c2: RistrettoPoint::identity() }
+    }
+
+    pub fn c1(&self) -> &RistrettoPoint {
+        &self.c1
+    }
+
+    pub fn c2(&self) -> &RistrettoPoint {
+        &self.c2
+    }
+}
+
+impl Add for Ciphertext {
+    type Output = Ciphertext;
+
+    fn add(self, other: Ciphertext) -> Ciphertext {
+        Ciphertext { c1: self.c1 + other.c1, c2: self.c2 + other.c2 }
+    }
+}
+
+impl AddAssign for Ciphertext {
+    fn add_assign(&mut self, other: Ciphertext) {
+        self.c1 += other.c1;
+        self.c2 += other.c2;
+    }
+}
+
+/// Sigma proof that a ciphertext and a Pedersen commitment hide the same
+/// value: knowledge of `v, k, r` with `c1 = k * B`, `c2 = v * B + k * pk` and
+/// `commitment = v * B + r * B_blinding`.
+#[derive(Clone)]
+pub(crate) struct EqualityProof {
+    nonce_commitments: [CompressedRistretto; 3],
+    responses: [Scalar; 3],
+}
+
+impl EqualityProof {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn prove<R: RngCore + CryptoRng>(
+        transcript: &mut Transcript,
+        pc_gens: &PedersenGens,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+        commitment: &RistrettoPoint,
+        value: &Scalar,
+        randomness: &Scalar,
+        blinding: &Scalar,
+        rng: &mut R,
+    ) -> EqualityProof {
+        let nonces = [Scalar::random(rng), Scalar::random(rng), Scalar::random(rng)];
+        let nonce_commitments = [
+            nonces[1] * pc_gens.B,
+            nonces[0] * pc_gens.B + nonces[1] * public_key.point,
+            nonces[0] * pc_gens.B + nonces[2] * pc_gens.B_blinding,
+        ]
+        .map(|point| point.compress());
+
+        let challenge = equality_challenge(transcript, public_key, ciphertext, commitment, &nonce_commitments);
+        let secrets = [value, randomness, blinding];
+
+        EqualityProof {
+            nonce_commitments,
+            responses: [0, 1, 2].map(|i| nonces[i] + challenge * secrets[i]),
+        }
+    }
+
+    pub(crate) fn verify(
+        &self,
+        transcript: &mut Transcript,
+        pc_gens: &PedersenGens,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+        commitment: &RistrettoPoint,
+    ) -> bool {
+        let challenge = equality_challenge(transcript, public_key, ciphertext, commitment, &self.nonce_commitments);
+        let nonce_commitments: Vec<RistrettoPoint> = match self.nonce_commitments.iter().map(|c| c.decompress()).collect() {
+            Some(points) => points,
+            None => return false,
+        };
+        let [value, randomness, blinding] = self.responses;
+
+        randomness * pc_gens.B == nonce_commitments[0] + challenge * ciphertext.c1
+            && value * pc_gens.B + randomness * public_key.point == nonce_commitments[1] + challenge * ciphertext.c2
+            && value * pc_gens.B + blinding * pc_gens.B_blinding == nonce_commitments[2] + challenge * commitment
+    }
+}
+
+fn equality_challenge(
+    transcript: &mut Transcript,
+    public_key: &PublicKey,
+    ciphertext: &Ciphertext,
+    commitment: &RistrettoPoint,
+    nonce_commitments: &[CompressedRistretto; 3],
+) -> Scalar {
+    transcript.append_message(b"equality public key", public_key.point.compress().as_bytes());
+    transcript.append_message(b"equality c1", ciphertext.c1.compress().as_bytes());
+    transcript.append_message(b"equality c2", ciphertext.c2.compress().as_bytes());
+    transcript.append_message(b"equality commitment", commitment.compress().as_bytes());
+    for nonce_commitment in nonce_commitments {
+        transcript.append_message(b"equality nonce", nonce_commitment.as_bytes());
+    }
+    let mut challenge = [0u8; 64];
+    transcript.challenge_bytes(b"equality challenge", &mut challenge);
+    Scalar::from_bytes_mod_order_wide(&challenge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_decryption() {
+        let pc_gens = PedersenGens::default();
+        let secret_key = SecretKey::generate(&mut thread_rng());
+        let public_key = secret_key.public_key(&pc_gens);
+
+        let ciphertext = public_key.encrypt(&pc_gens, &Scalar::from(42u64), &Scalar::random(&mut thread_rng()));
+        assert_eq!(secret_key.decrypt(&ciphertext), Scalar::from(42u64) * pc_gens.B);
+
+        let other_key = SecretKey::generate(&mut thread_rng());
+        assert_ne!(other_key.decrypt(&ciphertext), Scalar::from(42u64) * pc_gens.B);
+    }
+
+    #[test]
+    fn test_ciphertexts_add_homomorphically() {
+        let pc_gens = PedersenGens::default();
+        let secret_key = SecretKey::generate(&mut thread_rng());
+        let public_key = secret_key.public_key(&pc_gens);
+
+        let mut sum = Ciphertext::zero();
+        for value in [3u64, 5, 9] {
+            sum += public_key.encrypt(&pc_gens, &Scalar::from(value), &Scalar::random(&mut thread_rng()));
+        }
+        let negative = public_key.encrypt(&pc_gens, &-Scalar::from(7u64), &Scalar::random(&mut thread_rng()));
+
+        assert_eq!(secret_key.decrypt(&sum), Scalar::from(17u64) * pc_gens.B);
+        assert_eq!(secret_key.decrypt(&(sum + negative)), Scalar::from(10u64) * pc_gens.B);
+    }
+
+    #[test]
+    fn test_equality_proof() {
+        let pc_gens = PedersenGens::default();
+        let public_key = SecretKey::generate(&mut thread_rng()).public_key(&pc_gens);
+        let (value, randomness, blinding) = (Scalar::from(6u64), Scalar::random(&mut thread_rng()), Scalar::random(&mut thread_rng()));
+
+        let ciphertext = public_key.encrypt(&pc_gens, &value, &randomness);
+        let commitment = pc_gens.commit(value, blinding);
+        let proof = EqualityProof::prove(&mut Transcript::new(b"test"), &pc_gens, &public_key, &ciphertext, &commitment, &value, &randomness, &blinding, &mut thread_rng());
+        assert!(proof.verify(&mut Transcript::new(b"test"), &pc_gens, &public_key, &ciphertext, &commitment));
+
+        let other_key = SecretKey::generate(&mut thread_rng()).public_key(&pc_gens);
+        assert!(!proof.verify(&mut Transcript::new(b"test"), &pc_gens, &other_key, &ciphertext, &commitment));
+        assert!(!proof.verify(&mut Transcript::new(b"other"), &pc_gens, &public_key, &ciphertext, &commitment));
+    }
+
+    #[test]
+    fn test_equality_proof_rejects_different_values() {
+        let pc_gens = PedersenGens::default();
+        let public_key = SecretKey::generate(&mut thread_rng()).public_key(&pc_gens);
+        let (randomness, blinding) = (Scalar::random(&mut thread_rng()), Scalar::random(&mut thread_rng()));
+
+        // The ciphertext hides 1 while the commitment hides 0.
+        let ciphertext = public_key.encrypt(&pc_gens, &Scalar::one(), &randomness);
+        let commitment = pc_gens.commit(Scalar::zero(), blinding);
+        let proof = EqualityProof::prove(&mut Transcript::new(b"test"), &pc_gens, &public_key, &ciphertext, &commitment, &Scalar::zero(), &randomness, &blinding, &mut thread_rng());
+        assert!(!proof.verify(&mut Transcript::new(b"test"), &pc_gens, &public_key, &ciphertext, &commitment));
+    }
+}
@@ -0,0 +1,322 @@
+use ark_bls12_381::{Fr, G1Affine};
+use ark_ff::Zero;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use curve25519_dalek_ng::{ristretto::{CompressedRistretto, RistrettoPoint}, scalar::Scalar};
+use bulletproofs::{PedersenGens, RangeProof};
+#[cfg(feature = "r1cs")]
+use bulletproofs::r1cs::R1CSProof;
+use crate::ballot_validation::errors::DecodingError;
+
+/// Size of a compressed BLS12-381 G1 point.
+const G1_POINT_SIZE: usize = 48;
+
+/// Size of a BLS12-381 scalar.
+const FR_SIZE: usize = 32;
+
+/// Version of the wire format written by every `to_bytes` in this crate.
+pub const WIRE_VERSION: u8 = 1;
+
+/// Identifies the ballot scheme an encoded object belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchemeTag {
+    NoBudgetRatedVoting = 1,
+    MaxBudgetRatedVoting = 2,
+    ExactBudgetRatedVoting = 3,
+    QuadraticRatedVoting = 4,
+    ApprovalVoting = 5,
+    PluralityVoting = 6,
+    MultiWinnerVoting = 7,
+    WeakOrderingRankedVoting = 8,
+    RankedVoting = 9,
+    Tally = 10,
+    Mixnet = 11,
+}
+
+/// Distinguishes encoded setup parameters from encoded proofs of the same scheme.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectKind {
+    SetupParameters = 1,
+    Proof = 2,
+    ScoringOptions = 3,
+    DiscreteLogTable = 4,
+    EncryptedRanking = 5,
+    CastRanking = 6,
+    PublicKey = 7,
+}
+
+pub(crate) struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    pub(crate) fn new(tag: SchemeTag, kind: ObjectKind) -> Self {
+        Writer { buf: vec![WIRE_VERSION, tag as u8, kind as u8] }
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub(crate) fn i64(&mut self, value: i64) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub(crate) fn flag(&mut self, value: bool) -> &mut Self {
+        self.buf.push(value as u8);
+        self
+    }
+
+    pub(crate) fn optional_u64(&mut self, value: Option<u64>) -> &mut Self {
+        match value {
+            Some(value) => self.flag(true).u64(value),
+            None => self.flag(false),
+        }
+    }
+
+    pub(crate) fn point(&mut self, point: &CompressedRistretto) -> &mut Self {
+        self.buf.extend_from_slice(point.as_bytes());
+        self
+    }
+
+    pub(crate) fn scalar(&mut self, scalar: &Scalar) -> &mut Self {
+        self.buf.extend_from_slice(scalar.as_bytes());
+        self
+    }
+
+    pub(crate) fn points(&mut self, points: &[CompressedRistretto]) -> &mut Self {
+        self.u64(points.len() as u64);
+        points.iter().for_each(|point| {
+            self.point(point);
+        });
+        self
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.u64(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+        self
+    }
+
+    pub(crate) fn g1_point(&mut self, point: &G1Affine) -> &mut Self {
+        point
+            .serialize(&mut self.buf)
+            .expect("writing to a Vec cannot fail");
+        self
+    }
+
+    pub(crate) fn g1_points(&mut self, points: &[G1Affine]) -> &mut Self {
+        self.u64(points.len() as u64);
+        points.iter().for_each(|point| {
+            self.g1_point(point);
+        });
+        self
+    }
+
+    pub(crate) fn fr(&mut self, scalar: &Fr) -> &mut Self {
+        scalar
+            .serialize(&mut self.buf)
+            .expect("writing to a Vec cannot fail");
+        self
+    }
+
+    pub(crate) fn pc_gens(&mut self, pc_gens: &PedersenGens) -> &mut Self {
+        self.point(&pc_gens.B.compress()).point(&pc_gens.B_blinding.compress())
+    }
+
+    pub(crate) fn range_proof(&mut self, proof: &RangeProof) -> &mut Self {
+        let bytes = proof.to_bytes();
+        self.u64(bytes.len() as u64);
+        self.buf.extend_from_slice(&bytes);
+        self
+    }
+
+    #[cfg(feature = "r1cs")]
+    pub(crate) fn r1cs_proof(&mut self, proof: &R1CSProof) -> &mut Self {
+        let bytes = proof.to_bytes();
+        self.u64(bytes.len() as u64);
+        self.buf.extend_from_slice(&bytes);
+        self
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8], tag: SchemeTag, kind: ObjectKind) -> Result<Self, DecodingError> {
+        let mut reader = Reader { bytes };
+        let header = reader.take(3)?;
+
+        if header[0] != WIRE_VERSION {
+            return Err(DecodingError::UnsupportedVersion(header[0]));
+        }
+        if header[1] != tag as u8 || header[2] != kind as u8 {
+            return Err(DecodingError::UnexpectedTag);
+        }
+
+        Ok(reader)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodingError> {
+        if self.bytes.len() < len {
+            return Err(DecodingError::UnexpectedEnd);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, DecodingError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn i64(&mut self) -> Result<i64, DecodingError> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn flag(&mut self) -> Result<bool, DecodingError> {
+        match self.take(1)?[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodingError::InvalidLength),
+        }
+    }
+
+    pub(crate) fn optional_u64(&mut self) -> Result<Option<u64>, DecodingError> {
+        match self.flag()? {
+            true => Ok(Some(self.u64()?)),
+            false => Ok(None),
+        }
+    }
+
+    pub(crate) fn usize(&mut self) -> Result<usize, DecodingError> {
+        self.u64()?.try_into().map_err(|_| DecodingError::InvalidLength)
+    }
+
+    pub(crate) fn point(&mut self) -> Result<CompressedRistretto, DecodingError> {
+        let point = CompressedRistretto::from_slice(self.take(32)?);
+        point.decompress().ok_or(DecodingError::InvalidPoint)?;
+        Ok(point)
+    }
+
+    pub(crate) fn decompressed_point(&mut self) -> Result<RistrettoPoint, DecodingError> {
+        CompressedRistretto::from_slice(self.take(32)?)
+            .decompress()
+            .ok_or(DecodingError::InvalidPoint)
+    }
+
+    pub(crate) fn scalar(&mut self) -> Result<Scalar, DecodingError> {
+        Scalar::from_canonical_bytes(self.take(32)?.try_into().unwrap()).ok_or(DecodingError::InvalidScalar)
+    }
+
+    pub(crate) fn points(&mut self) -> Result<Vec<CompressedRistretto>, DecodingError> {
+        let len = self.usize()?;
+        if len > self.bytes.len() / 32 {
+            return Err(DecodingError::UnexpectedEnd);
+        }
+        (0..len).map(|_| self.point()).collect()
+    }
+
+    pub(crate) fn bytes(&mut self) -> Result<Vec<u8>, DecodingError> {
+        let len = self.usize()?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    /// Reads a compressed G1 point, rejecting points off the curve, outside
+    /// the prime-order subgroup, or at infinity.
+    pub(crate) fn g1_point(&mut self) -> Result<G1Affine, DecodingError> {
+        let point = G1Affine::deserialize(self.take(G1_POINT_SIZE)?).map_err(|_| DecodingError::InvalidPoint)?;
+        if point.is_zero() || !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+            return Err(DecodingError::InvalidPoint);
+        }
+        Ok(point)
+    }
+
+    pub(crate) fn g1_points(&mut self) -> Result<Vec<G1Affine>, DecodingError> {
+        let len = self.usize()?;
+        if len > self.bytes.len() / G1_POINT_SIZE {
+            return Err(DecodingError::UnexpectedEnd);
+        }
+        (0..len).map(|_| self.g1_point()).collect()
+    }
+
+    /// Reads a BLS12-381 scalar, rejecting encodings of values at or above
+    /// the group order.
+    pub(crate) fn fr(&mut self) -> Result<Fr, DecodingError> {
+        Fr::deserialize(self.take(FR_SIZE)?).map_err(|_| DecodingError::InvalidScalar)
+    }
+
+    pub(crate) fn pc_gens(&mut self) -> Result<PedersenGens, DecodingError> {
+        Ok(PedersenGens {
+            B: self.decompressed_point()?,
+            B_blinding: self.decompressed_point()?,
+        })
+    }
+
+    pub(crate) fn range_proof(&mut self) -> Result<RangeProof, DecodingError> {
+        let len = self.usize()?;
+        RangeProof::from_bytes(self.take(len)?).map_err(|_| DecodingError::InvalidRangeProof)
+    }
+
+    #[cfg(feature = "r1cs")]
+    pub(crate) fn r1cs_proof(&mut self) -> Result<R1CSProof, DecodingError> {
+        let len = self.usize()?;
+        R1CSProof::from_bytes(self.take(len)?).map_err(|_| DecodingError::InvalidConstraintProof)
+    }
+
+    pub(crate) fn finish(self) -> Result<(), DecodingError> {
+        if !self.bytes.is_empty() {
+            return Err(DecodingError::TrailingBytes);
+        }
+        Ok(())
+    }
+}
+
+/// Implements serde's `Serialize`/`Deserialize` through the type's canonical
+/// `to_bytes`/`from_bytes` encoding, so serde formats carry the same bytes.
+macro_rules! impl_serde_via_bytes {
+    ($type:ty, $name:literal) => {
+        impl serde::Serialize for $type {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(&self.to_bytes())
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $type {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct BytesVisitor;
+
+                impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+                    type Value = $type;
+
+                    fn expecting(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                        formatter.write_str(concat!("a valid ", $name))
+                    }
+
+                    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<$type, E> {
+                        <$type>::from_bytes(v).map_err(serde::de::Error::custom)
+                    }
+
+                    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<$type, A::Error> {
+                        let mut bytes: Vec<u8> = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                        while let Some(byte) = seq.next_element()? {
+                            bytes.push(byte);
+                        }
+                        <$type>::from_bytes(&bytes).map_err(serde::de::Error::custom)
+                    }
+                }
+
+                deserializer.deserialize_bytes(BytesVisitor)
+            }
+        }
+    };
+}
+
+pub(crate) use impl_serde_via_bytes;
@@ -0,0 +1,260 @@
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+use curve25519_dalek_ng::{ristretto::{CompressedRistretto, RistrettoPoint}, scalar::Scalar};
+use bulletproofs::PedersenGens;
+use crate::ballot_validation::election_context::ElectionContext;
+use crate::ballot_validation::elgamal::{Ciphertext, EqualityProof, PublicKey};
+use crate::ballot_validation::errors::VerificationError;
+use crate::ballot_validation::tally::{scalar_from_i128, CandidateOpening, TallyScheme};
+
+/// Rated ballot whose votes are also encrypted under the election key. The
+/// scheme proof validates the Pedersen commitments as before, and each
+/// ciphertext is proven to hide the same vote as its commitment, so the
+/// result can be obtained by decrypting the summed ciphertexts.
+pub struct EncryptedBallot<S: TallyScheme> {
+    proof: S::Proof,
+    ciphertexts: Vec<Ciphertext>,
+    equality_proofs: Vec<EqualityProof>,
+}
+
+impl<S: TallyScheme> Clone for EncryptedBallot<S>
+where
+    S::Proof: Clone,
+{
+    fn clone(&self) -> Self {
+        EncryptedBallot {
+            proof: self.proof.clone(),
+            ciphertexts: self.ciphertexts.clone(),
+            equality_proofs: self.equality_proofs.clone(),
+        }
+    }
+}
+
+impl<S: TallyScheme> EncryptedBallot<S> {
+    pub fn proof(&self) -> &S::Proof {
+        &self.proof
+    }
+
+    pub fn ciphertexts(&self) -> &[Ciphertext] {
+        &self.ciphertexts
+    }
+}
+
+pub fn generate_encrypted_vote<S: TallyScheme>(
+    params: &S::Params,
+    public_key: &PublicKey,
+    context: &ElectionContext,
+    ballot: &S::Ballot,
+) -> Result<EncryptedBallot<S>, S::Error> {
+    generate_encrypted_vote_with_rng(params, public_key, context, ballot, &mut OsRng)
+}
+
+pub fn generate_encrypted_vote_with_rng<S: TallyScheme, R: RngCore + CryptoRng>(
+    params: &S::Params,
+    public_key: &PublicKey,
+    context: &ElectionContext,
+    ballot: &S::Ballot,
+    rng: &mut R,
+) -> Result<EncryptedBallot<S>, S::Error> {
+    let (pc_gens, _, _) = S::tally_setup(params);
+    let (proof, openings) = S::generate_vote_with_openings(params, context, ballot, rng)?;
+
+    let mut transcript = context.scheme_transcript(b"encrypted ballot", S::setup_bytes(params));
+    let mut ciphertexts: Vec<Ciphertext> = Vec::with_capacity(openings.len());
+    let mut equality_proofs: Vec<EqualityProof> = Vec::with_capacity(openings.len());
+
+    for CandidateOpening { value, blinding } in openings {
+        let value: Scalar = scalar_from_i128(value);
+        let randomness: Scalar = Scalar::random(rng);
+        let ciphertext: Ciphertext = public_key.encrypt(&pc_gens, &value, &randomness);
+        let commitment: RistrettoPoint = pc_gens.commit(value, blinding);
+
+        equality_proofs.push(EqualityProof::prove(&mut transcript, &pc_gens, public_key, &ciphertext, &commitment, &value, &randomness, &blinding, rng));
+        ciphertexts.push(ciphertext);
+    }
+
+    Ok(EncryptedBallot { proof, ciphertexts, equality_proofs })
+}
+
+/// Verifies the scheme proof and that every ciphertext hides the vote in the
+/// matching commitment.
+pub fn verify_encrypted_vote<S: TallyScheme>(
+    params: &S::Params,
+    public_key: &PublicKey,
+    context: &ElectionContext,
+    ballot: &EncryptedBallot<S>,
+) -> Result<(), VerificationError> {
+    S::verify_proof(params, context, &ballot.proof)?;
+
+    let (pc_gens, _, offset) = S::tally_setup(params);
+    let commitments: Vec<RistrettoPoint> = offset_commitments(&pc_gens, offset, S::vote_commitments(&ballot.proof))?;
+    for len in [ballot.ciphertexts.len(), ballot.equality_proofs.len()] {
+        if len != commitments.len() {
+            return Err(VerificationError::LengthMismatch { expected: commitments.len(), actual: len });
+        }
+    }
+
+    let mut transcript = context.scheme_transcript(b"encrypted ballot", S::setup_bytes(params));
+    for (index, ((ciphertext, proof), commitment)) in ballot.ciphertexts.iter().zip(&ballot.equality_proofs).zip(&commitments).enumerate() {
+        if !proof.verify(&mut transcript, &pc_gens, public_key, ciphertext, commitment) {
+            return Err(VerificationError::EncryptionProofFailed { index });
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the scheme offset, so each commitment hides the vote itself like
+/// the ciphertext does.
+fn offset_commitments(
+    pc_gens: &PedersenGens,
+    offset: i64,
+    commitments: &[CompressedRistretto],
+) -> Result<Vec<RistrettoPoint>, VerificationError> {
+    let shift: RistrettoPoint = scalar_from_i128(offset as i128) * pc_gens.B;
+    commitments
+        .iter()
+        .map(|c| c.decompress().map(|point| point - shift).ok_or(VerificationError::DecompressionFailed))
+        .collect()
+}
+
+/// Per-candidate sum of the ciphertexts of accepted encrypted ballots.
+pub struct EncryptedTally {
+    sums: Vec<Ciphertext>,
+    ballot_count: u64,
+}
+
+impl EncryptedTally {
+    pub fn new(ballot_size: usize) -> EncryptedTally {
+        EncryptedTally {
+            sums: vec![Ciphertext::zero(); ballot_size],
+            ballot_count: 0,
+        }
+    }
+
+    /// Verifies the ballot and adds its ciphertexts to the sums.
+    pub fn add<S: TallyScheme>(
+        &mut self,
+        params: &S::Params,
+        public_key: &PublicKey,
+        context: &ElectionContext,
+        ballot: &EncryptedBallot<S>,
+    ) -> Result<(), VerificationError> {
+        verify_encrypted_vote(params, public_key, context, ballot)?;
+        self.add_verified(ballot)
+    }
+
+    /// Adds a ballot without checking its proofs, for ballots that have
+    /// already passed `verify_encrypted_vote`. An unverified ballot added
+    /// here silently corrupts the sums.
+    pub fn add_verified<S: TallyScheme>(&mut self, ballot: &EncryptedBallot<S>) -> Result<(), VerificationError> {
+        if ballot.ciphertexts.len() != self.sums.len() {
+            return Err(VerificationError::LengthMismatch { expected: self.sums.len(), actual: ballot.ciphertexts.len() });
+        }
+
+        for (sum, ciphertext) in self.sums.iter_mut().zip(&ballot.ciphertexts) {
+            *sum += *ciphertext;
+        }
+        self.ballot_count += 1;
+
+        Ok(())
+    }
+
+    pub fn ballot_count(&self) -> u64 {
+        self.ballot_count
+    }
+
+    pub fn sums(&self) -> &[Ciphertext] {
+        &self.sums
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+    use crate::ballot_validation::election_context::test_context;
+    use crate::ballot_validation::elgamal::SecretKey;
+    use crate::ballot_validation::rated_voting::max_budget::{self, MaxBudgetRatedVoting};
+    use crate::ballot_validation::rated_voting::no_budget::{self, NoBudgetRatedVoting};
+
+    #[test]
+    fn test_encrypted_vote_verifies() {
+        let params = max_budget::setup(10, None, None, 4, None).unwrap();
+        let public_key = SecretKey::generate(&mut thread_rng()).public_key(&PedersenGens::default());
+
+        let ballot = generate_encrypted_vote::<MaxBudgetRatedVoting>(&params, &public_key, &test_context(), &[4, 0, 3, 3]).unwrap();
+        assert!(verify_encrypted_vote(&params, &public_key, &test_context(), &ballot).is_ok());
+
+        let other_key = SecretKey::generate(&mut thread_rng()).public_key(&PedersenGens::default());
+        assert_eq!(verify_encrypted_vote(&params, &other_key, &test_context(), &ballot), Err(VerificationError::EncryptionProofFailed { index: 0 }));
+    }
+
+    #[test]
+    fn test_swapped_ciphertexts_are_rejected() {
+        let params = max_budget::setup(10, None, None, 4, None).unwrap();
+        let public_key = SecretKey::generate(&mut thread_rng()).public_key(&PedersenGens::default());
+
+        // The range proofs still hold, but the ciphertexts no longer match the commitments.
+        let mut ballot = generate_encrypted_vote::<MaxBudgetRatedVoting>(&params, &public_key, &test_context(), &[4, 0, 3, 3]).unwrap();
+        ballot.ciphertexts.swap(0, 1);
+        assert_eq!(verify_encrypted_vote(&params, &public_key, &test_context(), &ballot), Err(VerificationError::EncryptionProofFailed { index: 0 }));
+
+        let mut ballot = generate_encrypted_vote::<MaxBudgetRatedVoting>(&params, &public_key, &test_context(), &[4, 0, 3, 3]).unwrap();
+        ballot.ciphertexts.pop();
+        assert_eq!(verify_encrypted_vote(&params, &public_key, &test_context(), &ballot), Err(VerificationError::LengthMismatch { expected: 4, actual: 3 }));
+    }
+
+    #[test]
+    fn test_encrypted_tally_decrypts_to_totals() {
+        let pc_gens = PedersenGens::default();
+        let params = max_budget::setup(10, None, None, 2, None).unwrap();
+        let secret_key = SecretKey::generate(&mut thread_rng());
+        let public_key = secret_key.public_key(&pc_gens);
+
+        let mut tally = EncryptedTally::new(2);
+        for ballot in [[4, 6], [10, 0], [1, 1]] {
+            let ballot = generate_encrypted_vote::<MaxBudgetRatedVoting>(&params, &public_key, &test_context(), &ballot).unwrap();
+            tally.add(&params, &public_key, &test_context(), &ballot).unwrap();
+        }
+
+        assert_eq!(tally.ballot_count(), 3);
+        assert_eq!(secret_key.decrypt(&tally.sums()[0]), Scalar::from(15u64) * pc_gens.B);
+        assert_eq!(secret_key.decrypt(&tally.sums()[1]), Scalar::from(7u64) * pc_gens.B);
+    }
+
+    #[test]
+    fn test_no_budget_votes_are_encrypted_unshifted() {
+        let pc_gens = PedersenGens::default();
+        let params = no_budget::setup((-5, 5), 2, None).unwrap();
+        let secret_key = SecretKey::generate(&mut thread_rng());
+        let public_key = secret_key.public_key(&pc_gens);
+
+        let ballot = generate_encrypted_vote::<NoBudgetRatedVoting>(&params, &public_key, &test_context(), &[-5, 2]).unwrap();
+        assert!(verify_encrypted_vote(&params, &public_key, &test_context(), &ballot).is_ok());
+        assert_eq!(secret_key.decrypt(&ballot.ciphertexts()[0]), -Scalar::from(5u64) * pc_gens.B);
+        assert_eq!(secret_key.decrypt(&ballot.ciphertexts()[1]), Scalar::from(2u64) * pc_gens.B);
+    }
+
+    #[test]
+    fn test_encrypted_vote_is_bound_to_context() {
+        let params = no_budget::setup((-5, 5), 2, None).unwrap();
+        let public_key = SecretKey::generate(&mut thread_rng()).public_key(&PedersenGens::default());
+        let ballot = generate_encrypted_vote::<NoBudgetRatedVoting>(&params, &public_key, &test_context(), &[1, 2]).unwrap();
+
+        let other = ElectionContext::new(b"test election", b"test contest", b"other voter");
+        assert!(verify_encrypted_vote(&params, &public_key, &other, &ballot).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_tally_rejects_invalid_ballots() {
+        let params = max_budget::setup(10, None, None, 2, None).unwrap();
+        let public_key = SecretKey::generate(&mut thread_rng()).public_key(&PedersenGens::default());
+        let mut tally = EncryptedTally::new(2);
+
+        let mut ballot = generate_encrypted_vote::<MaxBudgetRatedVoting>(&params, &public_key, &test_context(), &[4, 6]).unwrap();
+        ballot.ciphertexts.swap(0, 1);
+        assert_eq!(tally.add(&params, &public_key, &test_context(), &ballot), Err(VerificationError::EncryptionProofFailed { index: 0 }));
+        assert_eq!(tally.ballot_count(), 0);
+        assert_eq!(tally.sums(), &[Ciphertext::zero(); 2]);
+    }
+}
@@ -0,0 +1,177 @@
+use core::fmt;
+use bulletproofs::ProofError;
+#[cfg(feature = "r1cs")]
+use bulletproofs::r1cs::R1CSError;
+
+/// Errors raised while setting up an election or generating a ballot proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BallotError {
+    InvalidSetup(String),
+    LengthMismatch { expected: usize, actual: usize },
+    VoteOutOfRange { index: usize },
+    Overflow,
+    BudgetExceeded { sum: u64, max: u64 },
+    BudgetBelowMinimum { sum: u64, min: u64 },
+    BudgetMismatch { sum: u64, budget: u64 },
+    InvalidPermutation(String),
+    DecompressionFailed,
+    RangeProofFailed(ProofError),
+    #[cfg(feature = "r1cs")]
+    ConstraintProofFailed(R1CSError),
+    PermutationProofFailed,
+}
+
+/// Reasons a ballot proof is rejected by a verifier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerificationError {
+    LengthMismatch { expected: usize, actual: usize },
+    DecompressionFailed,
+    CommitmentMismatch,
+    RangeProofFailed(ProofError),
+    #[cfg(feature = "r1cs")]
+    ConstraintProofFailed(R1CSError),
+    PermutationProofFailed,
+    OpeningProofFailed,
+    BitProofFailed { index: usize },
+    SetupMismatch,
+    TallyMismatch { candidate: usize },
+    EncryptionProofFailed { index: usize },
+    TotalOutOfRange { candidate: usize },
+    ShuffleProofFailed { server: usize },
+    RandomizerProofFailed { ballot: usize },
+    UndecodableBallot,
+}
+
+/// Ballots rejected by a batch verification, identified by their position in
+/// the batch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchVerificationError {
+    pub invalid: Vec<(usize, VerificationError)>,
+}
+
+/// Failures of threshold key generation or decryption, naming the trustee at
+/// fault where there is one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ThresholdError {
+    InvalidParameters(String),
+    UnknownTrustee { trustee: usize },
+    DuplicateTrustee { trustee: usize },
+    LengthMismatch { expected: usize, actual: usize },
+    InvalidDealing { dealer: usize },
+    InvalidShare { dealer: usize },
+    InvalidPartialDecryption { trustee: usize },
+    NotEnoughShares { needed: usize, actual: usize },
+}
+
+/// Reasons an encoded proof or set of setup parameters cannot be decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodingError {
+    UnsupportedVersion(u8),
+    UnexpectedTag,
+    UnexpectedEnd,
+    TrailingBytes,
+    InvalidLength,
+    InvalidPoint,
+    InvalidScalar,
+    InvalidRangeProof,
+    InvalidConstraintProof,
+    InvalidParameters(BallotError),
+}
+
+impl fmt::Display for BallotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BallotError::InvalidSetup(reason) => write!(f, "invalid setup parameters: {}", reason),
+            BallotError::LengthMismatch { expected, actual } => {
+                write!(f, "ballot length {} does not match expected number of candidates {}", actual, expected)
+            }
+            BallotError::VoteOutOfRange { index } => write!(f, "vote at position {} is outside the allowed range", index),
+            BallotError::Overflow => write!(f, "arithmetic overflow while processing ballot"),
+            BallotError::BudgetExceeded { sum, max } => write!(f, "ballot sum {} exceeds the maximum {}", sum, max),
+            BallotError::BudgetBelowMinimum { sum, min } => write!(f, "ballot sum {} is below the minimum {}", sum, min),
+            BallotError::BudgetMismatch { sum, budget } => write!(f, "ballot sum {} does not equal the budget {}", sum, budget),
+            BallotError::InvalidPermutation(reason) => write!(f, "ballot is not a valid permutation: {}", reason),
+            BallotError::DecompressionFailed => write!(f, "point decompression failed"),
+            BallotError::RangeProofFailed(e) => write!(f, "failed to create range proof: {}", e),
+            #[cfg(feature = "r1cs")]
+            BallotError::ConstraintProofFailed(e) => write!(f, "failed to create constraint system proof: {}", e),
+            BallotError::PermutationProofFailed => write!(f, "failed to create permutation proof"),
+        }
+    }
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationError::LengthMismatch { expected, actual } => {
+                write!(f, "proof contains {} commitments, expected {}", actual, expected)
+            }
+            VerificationError::DecompressionFailed => write!(f, "proof contains a malformed point"),
+            VerificationError::CommitmentMismatch => write!(f, "commitments do not match the proven values"),
+            VerificationError::RangeProofFailed(e) => write!(f, "range proof rejected: {}", e),
+            #[cfg(feature = "r1cs")]
+            VerificationError::ConstraintProofFailed(e) => write!(f, "constraint system proof rejected: {}", e),
+            VerificationError::PermutationProofFailed => write!(f, "permutation proof rejected"),
+            VerificationError::OpeningProofFailed => write!(f, "opening proof rejected"),
+            VerificationError::BitProofFailed { index } => write!(f, "vote at position {} is not proven to be 0 or 1", index),
+            VerificationError::SetupMismatch => write!(f, "setup parameters were not derived from the election identifier"),
+            VerificationError::TallyMismatch { candidate } => write!(f, "claimed total for candidate {} does not open the tallied commitments", candidate),
+            VerificationError::EncryptionProofFailed { index } => write!(f, "ciphertext at position {} is not proven to match its commitment", index),
+            VerificationError::TotalOutOfRange { candidate } => write!(f, "decrypted total for candidate {} is outside the possible range", candidate),
+            VerificationError::ShuffleProofFailed { server } => write!(f, "shuffle proof of mix server {} rejected", server),
+            VerificationError::RandomizerProofFailed { ballot } => write!(f, "cast ranking {} does not prove knowledge of its randomizer", ballot),
+            VerificationError::UndecodableBallot => write!(f, "decrypted entry is not a valid ballot"),
+        }
+    }
+}
+
+impl fmt::Display for BatchVerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.invalid.first() {
+            Some((index, e)) => write!(f, "{} ballots failed verification, the first at position {}: {}", self.invalid.len(), index, e),
+            None => write!(f, "batch verification failed"),
+        }
+    }
+}
+
+impl fmt::Display for ThresholdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThresholdError::InvalidParameters(reason) => write!(f, "invalid threshold parameters: {}", reason),
+            ThresholdError::UnknownTrustee { trustee } => write!(f, "trustee {} is not part of the election", trustee),
+            ThresholdError::DuplicateTrustee { trustee } => write!(f, "trustee {} appears more than once", trustee),
+            ThresholdError::LengthMismatch { expected, actual } => write!(f, "got {} entries, expected {}", actual, expected),
+            ThresholdError::InvalidDealing { dealer } => write!(f, "dealing of trustee {} rejected", dealer),
+            ThresholdError::InvalidShare { dealer } => write!(f, "share dealt by trustee {} does not match its commitments", dealer),
+            ThresholdError::InvalidPartialDecryption { trustee } => write!(f, "partial decryption of trustee {} rejected", trustee),
+            ThresholdError::NotEnoughShares { needed, actual } => write!(f, "{} partial decryptions given, {} needed", actual, needed),
+        }
+    }
+}
+
+impl fmt::Display for DecodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodingError::UnsupportedVersion(version) => write!(f, "unsupported wire format version {}", version),
+            DecodingError::UnexpectedTag => write!(f, "encoding belongs to a different scheme or object"),
+            DecodingError::UnexpectedEnd => write!(f, "encoding ended unexpectedly"),
+            DecodingError::TrailingBytes => write!(f, "encoding has trailing bytes"),
+            DecodingError::InvalidLength => write!(f, "encoding contains an invalid length"),
+            DecodingError::InvalidPoint => write!(f, "encoding contains an invalid point"),
+            DecodingError::InvalidScalar => write!(f, "encoding contains a non-canonical scalar"),
+            DecodingError::InvalidRangeProof => write!(f, "encoding contains a malformed range proof"),
+            DecodingError::InvalidConstraintProof => write!(f, "encoding contains a malformed constraint system proof"),
+            DecodingError::InvalidParameters(e) => write!(f, "encoded setup parameters are invalid: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BallotError {}
+
+impl std::error::Error for VerificationError {}
+
+impl std::error::Error for BatchVerificationError {}
+
+impl std::error::Error for ThresholdError {}
+
+impl std::error::Error for DecodingError {}
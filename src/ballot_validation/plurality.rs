@@ -0,0 +1,385 @@
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+use curve25519_dalek_ng::{ristretto::{CompressedRistretto, RistrettoPoint}, scalar::Scalar};
+use bulletproofs::PedersenGens;
+use crate::ballot_validation::ballot_scheme::{verify_batched, BallotScheme};
+use crate::ballot_validation::election_context::ElectionContext;
+use crate::ballot_validation::encoding::{impl_serde_via_bytes, ObjectKind, Reader, SchemeTag, Writer};
+use crate::ballot_validation::errors::{BallotError, BatchVerificationError, DecodingError, VerificationError};
+use crate::ballot_validation::rated_voting::common::{prove_bits, verify_bits, verify_bits_deferred, BitProof};
+use crate::ballot_validation::sigma::{BatchCheck, DiscreteLogProof};
+
+/// Setup for single-choice ballots: a one-hot vector over the candidates, or
+/// the all-zero vector when abstaining is allowed.
+pub struct SetupParameters {
+    pc_gens: PedersenGens,
+    ballot_size: usize,
+    allow_abstain: bool,
+    encoding: Vec<u8>,
+}
+
+#[derive(Clone)]
+enum SumProof {
+    /// The entries sum to exactly one.
+    ExactlyOne(DiscreteLogProof),
+    /// The entries sum to zero or one.
+    AtMostOne(BitProof),
+}
+
+#[derive(Clone)]
+pub struct PluralityVotingProof {
+    vote_commitments: Vec<CompressedRistretto>,
+    bit_proofs: Vec<BitProof>,
+    sum_proof: SumProof,
+}
+
+impl SetupParameters {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encoding.clone()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::PluralityVoting, ObjectKind::SetupParameters);
+        writer
+            .pc_gens(&self.pc_gens)
+            .u64(self.ballot_size as u64)
+            .flag(self.allow_abstain);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SetupParameters, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::PluralityVoting, ObjectKind::SetupParameters)?;
+        let pc_gens = reader.pc_gens()?;
+        let ballot_size = reader.usize()?;
+        let allow_abstain = reader.flag()?;
+        reader.finish()?;
+
+        setup(ballot_size, allow_abstain, Some(pc_gens)).map_err(DecodingError::InvalidParameters)
+    }
+}
+
+impl PluralityVotingProof {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::PluralityVoting, ObjectKind::Proof);
+        writer.points(&self.vote_commitments);
+        self.bit_proofs.iter().for_each(|proof| proof.write(&mut writer));
+        match &self.sum_proof {
+            SumProof::ExactlyOne(proof) => {
+                writer.flag(false);
+                proof.write(&mut writer);
+            }
+            SumProof::AtMostOne(proof) => {
+                writer.flag(true);
+                proof.write(&mut writer);
+            }
+        }
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<PluralityVotingProof, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::PluralityVoting, ObjectKind::Proof)?;
+        let vote_commitments = reader.points()?;
+        let bit_proofs = vote_commitments
+            .iter()
+            .map(|_| BitProof::read(&mut reader))
+            .collect::<Result<Vec<BitProof>, DecodingError>>()?;
+        let sum_proof = match reader.flag()? {
+            false => SumProof::ExactlyOne(DiscreteLogProof::read(&mut reader)?),
+            true => SumProof::AtMostOne(BitProof::read(&mut reader)?),
+        };
+        reader.finish()?;
+
+        Ok(PluralityVotingProof {
+            vote_commitments,
+            bit_proofs,
+            sum_proof
+        })
+    }
+}
+
+impl_serde_via_bytes!(SetupParameters, "plurality voting setup");
+impl_serde_via_bytes!(PluralityVotingProof, "plurality voting proof");
+
+pub struct PluralityVoting;
+
+impl BallotScheme for PluralityVoting {
+    type Params = SetupParameters;
+    type Ballot = [bool];
+    type Proof = PluralityVotingProof;
+    type Error = BallotError;
+
+    fn generate_vote_with_rng<R: RngCore + CryptoRng>(params: &SetupParameters, context: &ElectionContext, ballot: &[bool], rng: &mut R) -> Result<PluralityVotingProof, BallotError> {
+        generate_vote_with_rng(params, context, ballot, rng)
+    }
+
+    fn verify_proof(params: &SetupParameters, context: &ElectionContext, proof: &PluralityVotingProof) -> Result<(), VerificationError> {
+        verify_proof(params, context, proof)
+    }
+
+    fn verify_batch(params: &SetupParameters, ballots: &[(&ElectionContext, &PluralityVotingProof)]) -> Result<(), BatchVerificationError> {
+        verify_batch(params, ballots)
+    }
+}
+
+pub fn setup(
+    ballot_size: usize,
+    allow_abstain: bool,
+    pc_gens: Option<PedersenGens>
+) -> Result<SetupParameters, BallotError> {
+    if ballot_size == 0 {
+        return Err(BallotError::InvalidSetup("ballot_size must be greater than zero".into()));
+    }
+
+    let mut setup_params = SetupParameters {
+        pc_gens: pc_gens.unwrap_or_default(),
+        ballot_size,
+        allow_abstain,
+        encoding: Vec::new(),
+    };
+    setup_params.encoding = setup_params.encode();
+
+    Ok(setup_params)
+}
+
+pub fn generate_vote(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[bool],
+) -> Result<PluralityVotingProof, BallotError> {
+    generate_vote_with_rng(setup_params, context, ballot, &mut OsRng)
+}
+
+pub fn generate_vote_with_rng<R: RngCore + CryptoRng>(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[bool],
+    rng: &mut R,
+) -> Result<PluralityVotingProof, BallotError> {
+    if ballot.len() != setup_params.ballot_size {
+        return Err(BallotError::LengthMismatch { expected: setup_params.ballot_size, actual: ballot.len() });
+    }
+
+    let z: u64 = ballot.iter().filter(|&&vote| vote).count() as u64;
+    match (z, setup_params.allow_abstain) {
+        (1, _) | (0, true) => {}
+        (_, true) => return Err(BallotError::BudgetExceeded { sum: z, max: 1 }),
+        (_, false) => return Err(BallotError::BudgetMismatch { sum: z, budget: 1 }),
+    }
+
+    let mut transcript = context.scheme_transcript(b"plurality voting", &setup_params.encoding);
+    let (vote_commitments, ballot_blindings, bit_proofs) = prove_bits(&mut transcript, &setup_params.pc_gens, ballot, rng);
+
+    let z_blinding: Scalar = ballot_blindings.iter().sum();
+    let com_z: RistrettoPoint = vote_commitments.iter().sum();
+
+    let sum_proof = if setup_params.allow_abstain {
+        SumProof::AtMostOne(BitProof::prove(&mut transcript, &setup_params.pc_gens, &com_z, z == 1, &z_blinding, rng))
+    } else {
+        SumProof::ExactlyOne(DiscreteLogProof::prove(&mut transcript, &setup_params.pc_gens.B_blinding, &(com_z - setup_params.pc_gens.B), &z_blinding, rng))
+    };
+
+    Ok(PluralityVotingProof {
+        vote_commitments: vote_commitments.iter().map(|commitment| commitment.compress()).collect(),
+        bit_proofs,
+        sum_proof
+    })
+}
+
+pub fn verify_proof(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    validity_proof: &PluralityVotingProof,
+) -> Result<(), VerificationError> {
+    let mut transcript = context.scheme_transcript(b"plurality voting", &setup_params.encoding);
+    let vote_commitments: Vec<RistrettoPoint> = verify_bits(
+        &mut transcript,
+        &setup_params.pc_gens,
+        setup_params.ballot_size,
+        &validity_proof.vote_commitments,
+        &validity_proof.bit_proofs,
+    )?;
+    let com_z: RistrettoPoint = vote_commitments.iter().sum();
+
+    let valid = match (&validity_proof.sum_proof, setup_params.allow_abstain) {
+        (SumProof::AtMostOne(proof), true) => proof.verify(&mut transcript, &setup_params.pc_gens, &com_z),
+        (SumProof::ExactlyOne(proof), false) => proof.verify(&mut transcript, &setup_params.pc_gens.B_blinding, &(com_z - setup_params.pc_gens.B)),
+        _ => false,
+    };
+
+    if !valid {
+        return Err(VerificationError::OpeningProofFailed);
+    }
+
+    Ok(())
+}
+
+/// The bit proofs and sum proofs of the whole batch are checked together in
+/// one multiscalar multiplication.
+pub fn verify_batch(
+    setup_params: &SetupParameters,
+    ballots: &[(&ElectionContext, &PluralityVotingProof)],
+) -> Result<(), BatchVerificationError> {
+    verify_batched(
+        ballots,
+        BatchCheck::default(),
+        |batch, context, proof| {
+            let mut transcript = context.scheme_transcript(b"plurality voting", &setup_params.encoding);
+            let vote_commitments: Vec<RistrettoPoint> = verify_bits_deferred(
+                &mut transcript,
+                &setup_params.pc_gens,
+                setup_params.ballot_size,
+                &proof.vote_commitments,
+                &proof.bit_proofs,
+                batch,
+                &mut OsRng,
+            )?;
+            let com_z: RistrettoPoint = vote_commitments.iter().sum();
+
+            let valid = match (&proof.sum_proof, setup_params.allow_abstain) {
+                (SumProof::AtMostOne(sum_proof), true) => sum_proof.verify_deferred(&mut transcript, &setup_params.pc_gens, &com_z, batch, &mut OsRng),
+                (SumProof::ExactlyOne(sum_proof), false) => sum_proof.verify_deferred(&mut transcript, &setup_params.pc_gens.B_blinding, &(com_z - setup_params.pc_gens.B), batch, &mut OsRng),
+                _ => false,
+            };
+
+            if !valid {
+                return Err(VerificationError::OpeningProofFailed);
+            }
+
+            Ok(())
+        },
+        |batch| batch.verify(),
+        |context, proof| verify_proof(setup_params, context, proof),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ballot_validation::election_context::test_context;
+    use rand::thread_rng;
+
+    fn one_hot(ballot_size: usize, choice: usize) -> Vec<bool> {
+        (0..ballot_size).map(|i| i == choice).collect()
+    }
+
+    #[test]
+    fn test_single_choice_verifies() {
+        let setup_params = setup(5, false, None).unwrap();
+
+        for choice in 0..5 {
+            let proof = generate_vote(&setup_params, &test_context(), &one_hot(5, choice)).expect("Should generate proof");
+            assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok(), "One-hot ballot should verify");
+        }
+    }
+
+    #[test]
+    fn test_invalid_ballots_are_rejected() {
+        let setup_params = setup(3, false, None).unwrap();
+        assert_eq!(generate_vote(&setup_params, &test_context(), &[false; 3]).err(), Some(BallotError::BudgetMismatch { sum: 0, budget: 1 }));
+        assert_eq!(generate_vote(&setup_params, &test_context(), &[true, true, false]).err(), Some(BallotError::BudgetMismatch { sum: 2, budget: 1 }));
+        assert_eq!(generate_vote(&setup_params, &test_context(), &[true, false]).err(), Some(BallotError::LengthMismatch { expected: 3, actual: 2 }));
+
+        let abstain_params = setup(3, true, None).unwrap();
+        assert_eq!(generate_vote(&abstain_params, &test_context(), &[true, true, false]).err(), Some(BallotError::BudgetExceeded { sum: 2, max: 1 }));
+    }
+
+    #[test]
+    fn test_abstain() {
+        let setup_params = setup(3, true, None).unwrap();
+        let blank = generate_vote(&setup_params, &test_context(), &[false; 3]).unwrap();
+        assert!(verify_proof(&setup_params, &test_context(), &blank).is_ok(), "Blank ballot should verify when abstaining is allowed");
+
+        let choice = generate_vote(&setup_params, &test_context(), &one_hot(3, 2)).unwrap();
+        assert!(verify_proof(&setup_params, &test_context(), &choice).is_ok());
+
+    }
+
+    #[test]
+    fn test_blank_ballot_fails_exactly_one_check() {
+        let setup_params = setup(3, false, None).unwrap();
+        let mut transcript = test_context().scheme_transcript(b"plurality voting", &setup_params.encoding);
+        let (vote_commitments, blindings, bit_proofs) = prove_bits(&mut transcript, &setup_params.pc_gens, &[false; 3], &mut thread_rng());
+        let com_z: RistrettoPoint = vote_commitments.iter().sum();
+        let z_blinding: Scalar = blindings.iter().sum();
+
+        // The bit proofs hold, so only the sum proof can reject the blank ballot.
+        let mut proof = PluralityVotingProof {
+            vote_commitments: vote_commitments.iter().map(|commitment| commitment.compress()).collect(),
+            bit_proofs,
+            sum_proof: SumProof::ExactlyOne(DiscreteLogProof::prove(&mut transcript.clone(), &setup_params.pc_gens.B_blinding, &(com_z - setup_params.pc_gens.B), &z_blinding, &mut thread_rng())),
+        };
+        assert_eq!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::OpeningProofFailed));
+
+        proof.sum_proof = SumProof::AtMostOne(BitProof::prove(&mut transcript, &setup_params.pc_gens, &com_z, false, &z_blinding, &mut thread_rng()));
+        assert_eq!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::OpeningProofFailed));
+    }
+
+    #[test]
+    fn test_two_choices_fail_verification() {
+        let setup_params = setup(2, false, None).unwrap();
+        let mut proof = generate_vote(&setup_params, &test_context(), &[true, false]).unwrap();
+        let other = generate_vote(&setup_params, &test_context(), &[false, true]).unwrap();
+
+        proof.vote_commitments[1] = other.vote_commitments[1];
+        proof.bit_proofs[1] = other.bit_proofs[1].clone();
+        assert!(verify_proof(&setup_params, &test_context(), &proof).is_err(), "Ballot with two choices should not verify");
+    }
+
+    #[test]
+    fn test_batch_verification() {
+        let setup_params = setup(3, false, None).unwrap();
+        let contexts: Vec<ElectionContext> = (0..4)
+            .map(|voter| ElectionContext::new(b"test election", b"test contest", &[voter]))
+            .collect();
+        let mut proofs: Vec<PluralityVotingProof> = contexts
+            .iter()
+            .enumerate()
+            .map(|(voter, context)| generate_vote(&setup_params, context, &one_hot(3, voter % 3)).unwrap())
+            .collect();
+
+        let batch: Vec<(&ElectionContext, &PluralityVotingProof)> = contexts.iter().zip(&proofs).collect();
+        assert!(verify_batch(&setup_params, &batch).is_ok());
+
+        proofs[2].sum_proof = proofs[3].sum_proof.clone();
+        let batch: Vec<(&ElectionContext, &PluralityVotingProof)> = contexts.iter().zip(&proofs).collect();
+        assert_eq!(verify_batch(&setup_params, &batch).unwrap_err().invalid, vec![(2, VerificationError::OpeningProofFailed)]);
+
+        let batch = [(&contexts[0], &proofs[0]), (&contexts[0], &proofs[1])];
+        assert_eq!(verify_batch(&setup_params, &batch).unwrap_err().invalid, vec![(1, VerificationError::BitProofFailed { index: 0 })]);
+    }
+
+    #[test]
+    fn test_abstain_batch_verification() {
+        let setup_params = setup(3, true, None).unwrap();
+        let contexts: Vec<ElectionContext> = (0..3)
+            .map(|voter| ElectionContext::new(b"test election", b"test contest", &[voter]))
+            .collect();
+        let mut proofs: Vec<PluralityVotingProof> = contexts
+            .iter()
+            .zip([vec![false; 3], one_hot(3, 1), one_hot(3, 2)])
+            .map(|(context, ballot)| generate_vote(&setup_params, context, &ballot).unwrap())
+            .collect();
+
+        let batch: Vec<(&ElectionContext, &PluralityVotingProof)> = contexts.iter().zip(&proofs).collect();
+        assert!(verify_batch(&setup_params, &batch).is_ok());
+
+        proofs[1].sum_proof = proofs[2].sum_proof.clone();
+        let batch: Vec<(&ElectionContext, &PluralityVotingProof)> = contexts.iter().zip(&proofs).collect();
+        assert_eq!(verify_batch(&setup_params, &batch).unwrap_err().invalid, vec![(1, VerificationError::OpeningProofFailed)]);
+    }
+
+    #[test]
+    fn test_proof_is_bound_to_context_and_setup() {
+        let setup_params = setup(4, false, None).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), &[false, false, true, false]).unwrap();
+
+        for context in [
+            ElectionContext::new(b"test election", b"other contest", b"test voter"),
+            ElectionContext::new(b"test election", b"test contest", b"other voter"),
+        ] {
+            assert_eq!(verify_proof(&setup_params, &context, &proof), Err(VerificationError::BitProofFailed { index: 0 }));
+        }
+
+        let other = setup(4, true, None).unwrap();
+        assert_eq!(verify_proof(&other, &test_context(), &proof), Err(VerificationError::BitProofFailed { index: 0 }));
+    }
+}
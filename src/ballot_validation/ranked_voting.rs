@@ -1,22 +1,35 @@
 use core::iter;
-use ark_std::UniformRand;
-use ark_bls12_381::{Fr, G1Affine, G1Projective};
+use ark_bls12_381::{Fq, Fr, G1Affine, G1Projective};
 use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{PrimeField, Zero};
 use merlin::Transcript;
-use std::collections::HashMap;
-use ark_std::rand::{rngs::StdRng, SeedableRng};
+use std::collections::{HashMap, VecDeque};
+use rand::{rngs::OsRng, CryptoRng, RngCore};
 use curdleproofs::msm_accumulator::MsmAccumulator;
 use curdleproofs::same_permutation_argument::SamePermutationProof;
 use curdleproofs::util::{generate_blinders, msm};
-
+use crate::ballot_validation::ballot_scheme::{verify_batched, BallotScheme};
+use crate::ballot_validation::election_context::ElectionContext;
+use crate::ballot_validation::encoding::{impl_serde_via_bytes, ObjectKind, Reader, SchemeTag, Writer};
+use crate::ballot_validation::errors::{BallotError, BatchVerificationError, DecodingError, VerificationError};
+
+pub mod mixnet;
+#[cfg(feature = "r1cs")]
+pub mod weak_ordering;
+
+/// Setup for ranked ballots. Every generator is derived from the election
+/// identifier by hashing to the curve, so anyone can recompute the parameters
+/// with [`verify_setup`] and no one knows a discrete-log relation between them.
 pub struct SetupParameters {
+    election_id: Vec<u8>,
     crs_g_vec: Vec<G1Affine>,
     crs_h_vec: Vec<G1Affine>,
     crs_u: G1Projective,
     crs_g_sum: G1Affine,
     crs_h_sum: G1Affine,
     a: Options,
-    ballot_size: usize
+    ballot_size: usize,
+    encoding: Vec<u8>,
 }
 
 pub struct Options {
@@ -24,6 +37,8 @@ pub struct Options {
     scores_as_field_elements: Vec<Fr>
 }
 
+/// Curdleproofs 0.0.1 keeps the fields of `SamePermutationProof` private and
+/// implements no encoding for it, so this proof has no wire format yet.
 #[derive(Clone)] 
 pub struct RankedVotingProof {
     proof: SamePermutationProof,
@@ -31,46 +46,276 @@ pub struct RankedVotingProof {
     committed_permutation: G1Projective,
 }
 
-pub fn setup(ballot_size: usize) -> SetupParameters {
-    let mut rng: StdRng = StdRng::from_entropy();
+impl SetupParameters {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encoding.clone()
+    }
 
-    let crs_g_vec: Vec<_> = iter::repeat_with(|| G1Projective::rand(&mut rng).into_affine())
-        .take(ballot_size)
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::RankedVoting, ObjectKind::SetupParameters);
+        writer
+            .bytes(&self.election_id)
+            .u64(self.ballot_size as u64);
+        self.a.write(&mut writer);
+        writer
+            .g1_points(&self.crs_g_vec)
+            .g1_points(&self.crs_h_vec)
+            .g1_point(&self.crs_u.into_affine())
+            .g1_point(&self.crs_g_sum)
+            .g1_point(&self.crs_h_sum);
+        writer.into_bytes()
+    }
+
+    /// Decodes published parameters, checking every point is a valid subgroup
+    /// element and the sums match the generators. Whether the generators were
+    /// honestly derived is checked separately by [`verify_setup`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<SetupParameters, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::RankedVoting, ObjectKind::SetupParameters)?;
+        let election_id = reader.bytes()?;
+        let ballot_size = reader.usize()?;
+        let a = Options::read(&mut reader)?;
+        let crs_g_vec = reader.g1_points()?;
+        let crs_h_vec = reader.g1_points()?;
+        let crs_u = reader.g1_point()?.into_projective();
+        let crs_g_sum = reader.g1_point()?;
+        let crs_h_sum = reader.g1_point()?;
+        reader.finish()?;
+
+        if a.scores.len() != ballot_size || crs_g_vec.len() != ballot_size || crs_h_vec.len() != ballot_size {
+            return Err(DecodingError::InvalidLength);
+        }
+
+        if sum_affine_points(&crs_g_vec) != crs_g_sum || sum_affine_points(&crs_h_vec) != crs_h_sum {
+            return Err(DecodingError::InvalidParameters(BallotError::InvalidSetup("generator sums do not match the generators".into())));
+        }
+
+        let mut setup_params = SetupParameters {
+            election_id,
+            crs_g_vec,
+            crs_h_vec,
+            crs_u,
+            crs_g_sum,
+            crs_h_sum,
+            a,
+            ballot_size,
+            encoding: Vec::new(),
+        };
+        setup_params.encoding = setup_params.encode();
+
+        Ok(setup_params)
+    }
+}
+
+impl Options {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::RankedVoting, ObjectKind::ScoringOptions);
+        self.write(&mut writer);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Options, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::RankedVoting, ObjectKind::ScoringOptions)?;
+        let options = Options::read(&mut reader)?;
+        reader.finish()?;
+
+        Ok(options)
+    }
+
+    fn from_scores(scores: Vec<u32>) -> Options {
+        let scores_as_field_elements: Vec<Fr> = scores.iter().map(|&x| Fr::from(x)).collect();
+
+        Options {
+            scores,
+            scores_as_field_elements
+        }
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        writer.u64(self.scores.len() as u64);
+        self.scores.iter().for_each(|&score| {
+            writer.u64(score as u64);
+        });
+    }
+
+    fn read(reader: &mut Reader) -> Result<Options, DecodingError> {
+        let len = reader.usize()?;
+        let scores = (0..len)
+            .map(|_| {
+                let score = reader.u64()?;
+                u32::try_from(score).map_err(|_| DecodingError::InvalidParameters(BallotError::InvalidSetup("score does not fit in 32 bits".into())))
+            })
+            .collect::<Result<Vec<u32>, DecodingError>>()?;
+        validate_scores(&scores).map_err(DecodingError::InvalidParameters)?;
+
+        Ok(Options::from_scores(scores))
+    }
+}
+
+impl_serde_via_bytes!(SetupParameters, "ranked voting setup");
+impl_serde_via_bytes!(Options, "ranked voting scoring options");
+
+pub struct RankedVoting;
+
+impl BallotScheme for RankedVoting {
+    type Params = SetupParameters;
+    type Ballot = [u32];
+    type Proof = RankedVotingProof;
+    type Error = BallotError;
+
+    fn generate_vote_with_rng<R: RngCore + CryptoRng>(params: &SetupParameters, context: &ElectionContext, ballot: &[u32], rng: &mut R) -> Result<RankedVotingProof, BallotError> {
+        generate_vote_with_rng(params, context, ballot, rng)
+    }
+
+    fn verify_proof(params: &SetupParameters, context: &ElectionContext, proof: &RankedVotingProof) -> Result<(), VerificationError> {
+        verify_proof(params, context, proof)
+    }
+
+    fn verify_batch(params: &SetupParameters, ballots: &[(&ElectionContext, &RankedVotingProof)]) -> Result<(), BatchVerificationError> {
+        verify_batch(params, ballots)
+    }
+}
+
+const FORMULA_ONE_POINTS: [u32; 10] = [25, 18, 15, 12, 10, 8, 6, 4, 2, 1];
+
+const CRS_DOMAIN: &[u8] = b"zk ballot validation ranked voting crs";
+
+pub fn setup(ballot_size: usize, election_id: &[u8]) -> Result<SetupParameters, BallotError> {
+    setup_with_scores(borda_scores(ballot_size), election_id)
+}
+
+/// Setup for an arbitrary positional scoring rule. `scores[i]` is the score of
+/// the candidate ranked in position `i`, so the vector must be non-increasing.
+/// Repeated scores are allowed.
+pub fn setup_with_scores(scores: Vec<u32>, election_id: &[u8]) -> Result<SetupParameters, BallotError> {
+    validate_scores(&scores)?;
+    Ok(setup_from_scores(scores, election_id))
+}
+
+fn validate_scores(scores: &[u32]) -> Result<(), BallotError> {
+    if !scores.len().is_power_of_two() {
+        return Err(BallotError::InvalidSetup("number of scores must be a power of two".into()));
+    }
+
+    if scores.windows(2).any(|pair| pair[0] < pair[1]) {
+        return Err(BallotError::InvalidSetup("scores must be non-increasing".into()));
+    }
+
+    Ok(())
+}
+
+/// Setup for partial rankings where voters rank only their top `ranked`
+/// candidates. Ranked candidates score `ranked - 1` down to `0` and every
+/// unranked candidate scores `0`.
+pub fn setup_truncated(ballot_size: usize, ranked: usize, election_id: &[u8]) -> Result<SetupParameters, BallotError> {
+    if ranked == 0 || ranked > ballot_size {
+        return Err(BallotError::InvalidSetup("ranked must be between 1 and ballot_size".into()));
+    }
+
+    let scores: Vec<u32> = borda_scores(ranked)
+        .into_iter()
+        .chain(iter::repeat_n(0, ballot_size - ranked))
         .collect();
-    let crs_h_vec: Vec<_> = iter::repeat_with(|| G1Projective::rand(&mut rng).into_affine())
+    setup_with_scores(scores, election_id)
+}
+
+/// Borda scores `n - 1, ..., 1, 0`.
+pub fn borda_scores(ballot_size: usize) -> Vec<u32> {
+    (0..ballot_size as u32).rev().collect()
+}
+
+/// Dowdall scores `1, 1/2, ..., 1/n`, scaled by `lcm(1..=n)` so they are integers.
+pub fn dowdall_scores(ballot_size: usize) -> Result<Vec<u32>, BallotError> {
+    let scale: u32 = (1..=ballot_size as u64)
+        .try_fold(1u64, |lcm, i| {
+            let lcm = lcm / gcd(lcm, i) * i;
+            (lcm <= u32::MAX as u64).then_some(lcm)
+        })
+        .ok_or_else(|| BallotError::InvalidSetup("Dowdall scores do not fit in 32 bits for this many candidates".into()))? as u32;
+
+    Ok((1..=ballot_size as u32).map(|position| scale / position).collect())
+}
+
+/// Formula One points `25, 18, 15, 12, 10, 8, 6, 4, 2, 1`, with zero for
+/// every position below tenth.
+pub fn formula_one_scores(ballot_size: usize) -> Vec<u32> {
+    FORMULA_ONE_POINTS
+        .into_iter()
+        .chain(iter::repeat(0))
         .take(ballot_size)
+        .collect()
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Recomputes the generators for `election_id` and checks that the published
+/// parameters use exactly those, so they hide no trapdoor.
+pub fn verify_setup(setup_params: &SetupParameters, election_id: &[u8]) -> Result<(), VerificationError> {
+    let expected = setup_from_scores(setup_params.a.scores.clone(), election_id);
+
+    let matches = setup_params.election_id == expected.election_id
+        && setup_params.ballot_size == expected.ballot_size
+        && setup_params.crs_g_vec == expected.crs_g_vec
+        && setup_params.crs_h_vec == expected.crs_h_vec
+        && setup_params.crs_u == expected.crs_u
+        && setup_params.crs_g_sum == expected.crs_g_sum
+        && setup_params.crs_h_sum == expected.crs_h_sum
+        && setup_params.a.scores_as_field_elements == expected.a.scores_as_field_elements;
+
+    if !matches {
+        return Err(VerificationError::SetupMismatch);
+    }
+
+    Ok(())
+}
+
+fn setup_from_scores(scores: Vec<u32>, election_id: &[u8]) -> SetupParameters {
+    let ballot_size = scores.len();
+
+    let crs_g_vec: Vec<G1Affine> = (0..ballot_size as u64)
+        .map(|index| hash_to_g1(election_id, b"g", index))
+        .collect();
+    let crs_h_vec: Vec<G1Affine> = (0..ballot_size as u64)
+        .map(|index| hash_to_g1(election_id, b"h", index))
         .collect();
 
-    let crs_u = G1Projective::rand(&mut rng);
+    let crs_u = hash_to_g1(election_id, b"u", 0).into_projective();
     let crs_g_sum = sum_affine_points(&crs_g_vec);
     let crs_h_sum = sum_affine_points(&crs_h_vec);
 
-    let scores: Vec<u32> = (0..ballot_size as u32).rev().collect();
-    let scores_as_field_elements: Vec<Fr> = scores.iter().map(|&x| Fr::from(x)).collect();
-
-    let a = Options {
-        scores,
-        scores_as_field_elements
-    };
-
-    SetupParameters {
+    let mut setup_params = SetupParameters {
+        election_id: election_id.to_vec(),
         crs_g_vec,
         crs_h_vec,
         crs_u,
         crs_g_sum,
         crs_h_sum,
-        a,
-        ballot_size
-    }
+        a: Options::from_scores(scores),
+        ballot_size,
+        encoding: Vec::new(),
+    };
+    setup_params.encoding = setup_params.encode();
+
+    setup_params
 }
 
 pub fn generate_vote(
-    scores: &Vec<u32>,
     setup_params: &SetupParameters,
-) -> Result<RankedVotingProof, String> {
-    let permutation= find_permutation(&setup_params.a.scores, &scores)?;
+    context: &ElectionContext,
+    scores: &[u32],
+) -> Result<RankedVotingProof, BallotError> {
+    generate_vote_with_rng(setup_params, context, scores, &mut OsRng)
+}
+
+pub fn generate_vote_with_rng<R: RngCore + CryptoRng>(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    scores: &[u32],
+    rng: &mut R,
+) -> Result<RankedVotingProof, BallotError> {
+    let permutation= find_permutation(&setup_params.a.scores, scores)?;
     
-    let mut rng: StdRng = StdRng::seed_from_u64(0u64);
 
     let ballot_size = scores.len();
 
@@ -79,8 +324,8 @@ pub fn generate_vote(
     .map(|i| Fr::from(permutation[i] as u64))
     .collect();
 
-    let committed_ballot_blinders = generate_blinders(&mut rng, ballot_size);
-    let committed_permutation_blinders = generate_blinders(&mut rng, ballot_size);
+    let committed_ballot_blinders = generate_blinders(rng, ballot_size);
+    let committed_permutation_blinders = generate_blinders(rng, ballot_size);
 
     let committed_ballot = msm(&setup_params.crs_g_vec, &ballot_fr) + msm(&setup_params.crs_h_vec, &committed_ballot_blinders);
     let committed_permutation: ark_ec::short_weierstrass_jacobian::GroupProjective<ark_bls12_381::g1::Parameters> = msm(&setup_params.crs_g_vec, &permutation_as_fr) + msm(&setup_params.crs_h_vec, &committed_permutation_blinders);
@@ -95,8 +340,8 @@ pub fn generate_vote(
         permutation,
         committed_ballot_blinders,
         committed_permutation_blinders,
-        &mut Transcript::new(b"sameperm"),
-        &mut rng,
+        &mut context.scheme_transcript(b"sameperm", &setup_params.encoding),
+        rng,
     );
 
     Ok(RankedVotingProof {
@@ -106,11 +351,35 @@ pub fn generate_vote(
     })
 }
 
-pub fn verify_proof(proof: &RankedVotingProof, setup_params: &SetupParameters) -> bool {
-    let mut rng: StdRng = StdRng::seed_from_u64(0u64);
+pub fn verify_proof(setup_params: &SetupParameters, context: &ElectionContext, proof: &RankedVotingProof) -> Result<(), VerificationError> {
     let mut msm_accumulator = MsmAccumulator::default();
+    accumulate_proof(setup_params, context, proof, &mut msm_accumulator)?;
+
+    msm_accumulator.verify().map_err(|_| VerificationError::PermutationProofFailed)
+}
 
-    let verification = proof.proof.verify(
+/// Verifies many ballots with a single multi-exponentiation: every proof feeds
+/// the same `MsmAccumulator`, which weights each check with fresh randomness.
+pub fn verify_batch(
+    setup_params: &SetupParameters,
+    ballots: &[(&ElectionContext, &RankedVotingProof)],
+) -> Result<(), BatchVerificationError> {
+    verify_batched(
+        ballots,
+        MsmAccumulator::default(),
+        |msm_accumulator, context, proof| accumulate_proof(setup_params, context, proof, msm_accumulator),
+        |msm_accumulator| msm_accumulator.verify().is_ok(),
+        |context, proof| verify_proof(setup_params, context, proof),
+    )
+}
+
+fn accumulate_proof(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    proof: &RankedVotingProof,
+    msm_accumulator: &mut MsmAccumulator,
+) -> Result<(), VerificationError> {
+    proof.proof.verify(
         &setup_params.crs_g_vec,
         &setup_params.crs_h_vec,
         &setup_params.crs_u,
@@ -120,12 +389,34 @@ pub fn verify_proof(proof: &RankedVotingProof, setup_params: &SetupParameters) -
         &proof.committed_permutation,
         &setup_params.a.scores_as_field_elements,
         setup_params.ballot_size,
-        &mut Transcript::new(b"sameperm"),
-        &mut msm_accumulator,
-        &mut rng,
-    );
+        &mut context.scheme_transcript(b"sameperm", &setup_params.encoding),
+        msm_accumulator,
+        &mut OsRng,
+    )
+    .map_err(|_| VerificationError::PermutationProofFailed)
+}
 
-    verification.is_ok() && msm_accumulator.verify().is_ok()
+/// Try-and-increment hash to G1: hashes the inputs and a counter to an
+/// x-coordinate until it lands on the curve, then clears the cofactor.
+fn hash_to_g1(election_id: &[u8], label: &'static [u8], index: u64) -> G1Affine {
+    (0u64..)
+        .find_map(|counter| {
+            let mut transcript = Transcript::new(CRS_DOMAIN);
+            transcript.append_message(b"election id", election_id);
+            transcript.append_message(b"generator", label);
+            transcript.append_u64(b"index", index);
+            transcript.append_u64(b"counter", counter);
+
+            let mut x_bytes = [0u8; 64];
+            transcript.challenge_bytes(b"x coordinate", &mut x_bytes);
+            let mut sign = [0u8; 1];
+            transcript.challenge_bytes(b"y sign", &mut sign);
+
+            G1Affine::get_point_from_x(Fq::from_le_bytes_mod_order(&x_bytes), sign[0] & 1 == 1)
+                .map(|point| point.mul_by_cofactor_to_projective().into_affine())
+                .filter(|point| !point.is_zero())
+        })
+        .expect("about half of all x-coordinates are on the curve")
 }
 
 fn sum_affine_points(affine_points: &[G1Affine]) -> G1Affine {
@@ -137,25 +428,26 @@ fn sum_affine_points(affine_points: &[G1Affine]) -> G1Affine {
 }
 
 
-fn find_permutation(vec_a: &[u32], vec_b: &[u32]) -> Result<Vec<u32>, String> {
+fn find_permutation(vec_a: &[u32], vec_b: &[u32]) -> Result<Vec<u32>, BallotError> {
     if vec_a.len() != vec_b.len() {
-        return Err("Vectors must be the same length".to_string());
+        return Err(BallotError::LengthMismatch { expected: vec_a.len(), actual: vec_b.len() });
     }
 
-    let mut index_map: HashMap<u32, usize> = HashMap::new();
+    // Repeated values in vec_a (e.g. the zeros of a truncated ranking) are
+    // handed out in order, so each index of vec_a is used exactly once.
+    let mut index_map: HashMap<u32, VecDeque<usize>> = HashMap::new();
     for (i, &val) in vec_a.iter().enumerate() {
-        if index_map.insert(val, i).is_some() {
-            return Err(format!("Duplicate value {} in vec_a not allowed", val));
-        }
+        index_map.entry(val).or_default().push_back(i);
     }
 
     let permutation: Vec<u32> = vec_b
         .iter()
         .map(|&val| {
             index_map
-                .get(&val)
-                .map(|&i| i as u32)
-                .ok_or_else(|| format!("Value {} in vec_b not found in vec_a", val))
+                .get_mut(&val)
+                .and_then(|indices| indices.pop_front())
+                .map(|i| i as u32)
+                .ok_or_else(|| BallotError::InvalidPermutation(format!("Value {} in vec_b not found in vec_a or used more than once", val)))
         })
         .collect::<Result<_, _>>()?;
 
@@ -167,44 +459,129 @@ fn find_permutation(vec_a: &[u32], vec_b: &[u32]) -> Result<Vec<u32>, String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ballot_validation::election_context::test_context;
+    use ark_std::UniformRand;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const ELECTION_ID: &[u8] = b"test election";
 
     #[test]
     fn test_valid_permutation_proof() {
         let ballot = vec![1, 0, 2, 3];
-        let setup_params = setup(ballot.len());
+        let setup_params = setup(ballot.len(), ELECTION_ID).unwrap();
 
-        let proof = generate_vote(&ballot, &setup_params).expect("Should generate proof");
-        assert!(verify_proof(&proof, &setup_params), "Proof should verify for valid permutation");
+        let proof = generate_vote(&setup_params, &test_context(), &ballot).expect("Should generate proof");
+        assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok(), "Proof should verify for valid permutation");
     }
 
     #[test]
     fn test_invalid_permutation_proof() {
         let ballot = vec![1, 0, 2, 2];
-        let setup_params = setup(ballot.len());
+        let setup_params = setup(ballot.len(), ELECTION_ID).unwrap();
 
-        let result = generate_vote(&ballot, &setup_params);
-        assert!(result.is_err(), "Should fail to generate proof for invalid permutation");
+        let result = generate_vote(&setup_params, &test_context(), &ballot);
+        assert!(matches!(result, Err(BallotError::InvalidPermutation(_))), "Should fail to generate proof for invalid permutation");
     }
 
     #[test]
     fn test_mismatched_vector_length() {
         let ballot = vec![2, 3];
-        let setup_params = setup(3);
+        let setup_params = setup(4, ELECTION_ID).unwrap();
 
-        let result = generate_vote(&ballot, &setup_params);
-        assert!(result.is_err(), "Should fail due to mismatched lengths");
+        let result = generate_vote(&setup_params, &test_context(), &ballot);
+        assert!(matches!(result, Err(BallotError::LengthMismatch { expected: 4, actual: 2 })), "Should fail due to mismatched lengths");
     }
 
     #[test]
     fn test_proof_integrity_fails_on_tamper() {
         let ballot = vec![0, 1, 2, 3];
-        let setup_params = setup(ballot.len());
+        let setup_params = setup(ballot.len(), ELECTION_ID).unwrap();
 
-        let mut proof = generate_vote(&ballot, &setup_params).expect("Proof should be valid");
+        let mut proof = generate_vote(&setup_params, &test_context(), &ballot).expect("Proof should be valid");
 
         proof.committed_permutation = G1Projective::rand(&mut StdRng::seed_from_u64(999));
 
-        assert!(!verify_proof(&proof, &setup_params), "Tampered proof should not verify");
+        assert_eq!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::PermutationProofFailed), "Tampered proof should not verify");
+    }
+
+    #[test]
+    fn test_identical_ballots_have_distinct_commitments() {
+        let ballot = vec![2, 0, 3, 1];
+        let setup_params = setup(ballot.len(), ELECTION_ID).unwrap();
+
+        let first = generate_vote(&setup_params, &test_context(), &ballot).unwrap();
+        let second = generate_vote(&setup_params, &test_context(), &ballot).unwrap();
+        assert!(verify_proof(&setup_params, &test_context(), &first).is_ok());
+        assert!(verify_proof(&setup_params, &test_context(), &second).is_ok());
+        assert_ne!(first.committed_ballot, second.committed_ballot, "Blinders must not repeat across voters");
+        assert_ne!(first.committed_permutation, second.committed_permutation);
+
+        let seeded = generate_vote_with_rng(&setup_params, &test_context(), &ballot, &mut StdRng::seed_from_u64(7)).unwrap();
+        let reseeded = generate_vote_with_rng(&setup_params, &test_context(), &ballot, &mut StdRng::seed_from_u64(7)).unwrap();
+        assert!(verify_proof(&setup_params, &test_context(), &seeded).is_ok());
+        assert_eq!(seeded.committed_ballot, reseeded.committed_ballot, "An injected rng should fully determine the blinders");
+    }
+
+    #[test]
+    fn test_setup_is_reproducible() {
+        let setup_params = setup(4, ELECTION_ID).unwrap();
+        let again = setup(4, ELECTION_ID).unwrap();
+        assert!(setup_params.crs_g_vec == again.crs_g_vec && setup_params.crs_h_vec == again.crs_h_vec && setup_params.crs_u == again.crs_u);
+        assert!(verify_setup(&setup_params, ELECTION_ID).is_ok(), "Honest parameters should pass third-party verification");
+
+        let proof = generate_vote(&setup_params, &test_context(), &[0, 3, 1, 2]).unwrap();
+        assert!(verify_proof(&again, &test_context(), &proof).is_ok(), "Independently derived parameters should verify the same proof");
+
+        let other = setup(4, b"other election").unwrap();
+        assert!(other.crs_g_vec.iter().all(|g| !setup_params.crs_g_vec.contains(g)));
+        assert_eq!(verify_setup(&other, ELECTION_ID), Err(VerificationError::SetupMismatch));
+    }
+
+    #[test]
+    fn test_tampered_setup_is_rejected() {
+        let mut setup_params = setup(4, ELECTION_ID).unwrap();
+        setup_params.crs_h_vec[2] = G1Projective::rand(&mut StdRng::seed_from_u64(1)).into_affine();
+        assert_eq!(verify_setup(&setup_params, ELECTION_ID), Err(VerificationError::SetupMismatch));
+
+        let mut setup_params = setup(4, ELECTION_ID).unwrap();
+        setup_params.crs_u = G1Projective::rand(&mut StdRng::seed_from_u64(2));
+        assert_eq!(verify_setup(&setup_params, ELECTION_ID), Err(VerificationError::SetupMismatch));
+
+        for point in setup(8, ELECTION_ID).unwrap().crs_g_vec {
+            assert!(point.is_on_curve() && point.is_in_correct_subgroup_assuming_on_curve() && !point.is_zero());
+        }
+    }
+
+    #[test]
+    fn test_setup_round_trip() {
+        let setup_params = setup_truncated(8, 5, ELECTION_ID).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), &[0, 4, 0, 3, 2, 0, 1, 0]).unwrap();
+
+        let decoded = SetupParameters::from_bytes(&setup_params.to_bytes()).unwrap();
+        assert_eq!(decoded.a.scores, setup_params.a.scores);
+        assert!(verify_setup(&decoded, ELECTION_ID).is_ok());
+        assert!(verify_proof(&decoded, &test_context(), &proof).is_ok(), "Proof should verify against imported parameters");
+
+        let options = Options::from_bytes(&setup_params.a.to_bytes()).unwrap();
+        assert_eq!(options.scores_as_field_elements, setup_params.a.scores_as_field_elements);
+    }
+
+    #[test]
+    fn test_malformed_setup_encodings_are_rejected() {
+        let mut setup_params = setup(4, ELECTION_ID).unwrap();
+        setup_params.crs_g_sum = setup_params.crs_h_sum;
+        assert!(matches!(SetupParameters::from_bytes(&setup_params.encode()), Err(DecodingError::InvalidParameters(_))));
+
+        let mut bytes = setup(4, ELECTION_ID).unwrap().to_bytes();
+        let last = bytes.len() - 1;
+        bytes[last - 47..].fill(0xff);
+        assert_eq!(SetupParameters::from_bytes(&bytes).err(), Some(DecodingError::InvalidPoint));
+
+        let bytes = setup(4, ELECTION_ID).unwrap().to_bytes();
+        assert_eq!(SetupParameters::from_bytes(&bytes[..bytes.len() - 1]).err(), Some(DecodingError::UnexpectedEnd));
+
+        let increasing = Options::from_scores(vec![0, 1, 2, 3]);
+        assert!(matches!(Options::from_bytes(&increasing.to_bytes()), Err(DecodingError::InvalidParameters(_))));
     }
 
     #[test]
@@ -221,4 +598,113 @@ mod tests {
             assert_eq!(a_vec[sigma_i], b_vec[i], "Mismatch at i={}", i);
         }
     }
+
+    #[test]
+    fn test_truncated_ranking() {
+        let setup_params = setup_truncated(8, 3, ELECTION_ID).unwrap();
+        assert_eq!(setup_params.a.scores, vec![2, 1, 0, 0, 0, 0, 0, 0]);
+
+        let proof = generate_vote(&setup_params, &test_context(), &[0, 2, 0, 0, 0, 1, 0, 0]).expect("Should generate proof");
+        assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok(), "Partial ranking should verify");
+
+        let result = generate_vote(&setup_params, &test_context(), &[3, 2, 0, 0, 0, 1, 0, 0]);
+        assert!(matches!(result, Err(BallotError::InvalidPermutation(_))), "Ranking more than k candidates should fail");
+    }
+
+    #[test]
+    fn test_truncated_setup_bounds() {
+        assert!(matches!(setup_truncated(4, 0, ELECTION_ID), Err(BallotError::InvalidSetup(_))));
+        assert!(matches!(setup_truncated(4, 5, ELECTION_ID), Err(BallotError::InvalidSetup(_))));
+        assert!(matches!(setup_truncated(6, 3, ELECTION_ID), Err(BallotError::InvalidSetup(_))));
+        assert_eq!(setup_truncated(4, 4, ELECTION_ID).unwrap().a.scores, setup(4, ELECTION_ID).unwrap().a.scores);
+    }
+
+    #[test]
+    fn test_find_permutation_with_duplicates() {
+        let a_vec = vec![2, 1, 0, 0, 0];
+        let b_vec = vec![0, 0, 1, 0, 2];
+
+        let permutation = find_permutation(&a_vec, &b_vec).expect("Should handle repeated values");
+
+        let mut used = permutation.clone();
+        used.sort();
+        assert_eq!(used, vec![0, 1, 2, 3, 4], "Every index should be used exactly once");
+        for i in 0..a_vec.len() {
+            assert_eq!(a_vec[permutation[i] as usize], b_vec[i], "Mismatch at i={}", i);
+        }
+
+        assert!(matches!(find_permutation(&a_vec, &[0, 0, 0, 0, 2]), Err(BallotError::InvalidPermutation(_))));
+    }
+
+    #[test]
+    fn test_custom_scores() {
+        let setup_params = setup_with_scores(formula_one_scores(16), ELECTION_ID).unwrap();
+        let mut ballot = formula_one_scores(16);
+        ballot.reverse();
+
+        let proof = generate_vote(&setup_params, &test_context(), &ballot).expect("Should generate proof");
+        assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok(), "Formula One ballot should verify");
+
+        let borda = setup(16, ELECTION_ID).unwrap();
+        assert_eq!(verify_proof(&borda, &test_context(), &proof), Err(VerificationError::PermutationProofFailed));
+    }
+
+    #[test]
+    fn test_score_presets() {
+        assert_eq!(borda_scores(4), vec![3, 2, 1, 0]);
+        assert_eq!(dowdall_scores(4).unwrap(), vec![12, 6, 4, 3]);
+        assert_eq!(formula_one_scores(4), vec![25, 18, 15, 12]);
+        assert_eq!(formula_one_scores(12)[9..], [1, 0, 0]);
+        assert!(dowdall_scores(16).is_ok());
+        assert!(matches!(dowdall_scores(32), Err(BallotError::InvalidSetup(_))));
+    }
+
+    #[test]
+    fn test_malformed_scores_are_rejected() {
+        assert!(matches!(setup_with_scores(vec![], ELECTION_ID), Err(BallotError::InvalidSetup(_))));
+        assert!(matches!(setup_with_scores(vec![3, 2, 1], ELECTION_ID), Err(BallotError::InvalidSetup(_))));
+        assert!(matches!(setup_with_scores(vec![3, 2, 4, 0], ELECTION_ID), Err(BallotError::InvalidSetup(_))));
+        assert!(setup_with_scores(vec![5, 5, 1, 0], ELECTION_ID).is_ok());
+
+        assert!(matches!(setup(3, ELECTION_ID), Err(BallotError::InvalidSetup(_))));
+        assert!(matches!(setup(0, ELECTION_ID), Err(BallotError::InvalidSetup(_))));
+    }
+
+    #[test]
+    fn test_batch_verification() {
+        let setup_params = setup(4, ELECTION_ID).unwrap();
+        let contexts: Vec<ElectionContext> = (0..4)
+            .map(|voter| ElectionContext::new(ELECTION_ID, b"test contest", &[voter]))
+            .collect();
+        let mut proofs: Vec<RankedVotingProof> = contexts
+            .iter()
+            .map(|context| generate_vote(&setup_params, context, &[1, 3, 0, 2]).unwrap())
+            .collect();
+
+        let batch: Vec<(&ElectionContext, &RankedVotingProof)> = contexts.iter().zip(&proofs).collect();
+        assert!(verify_batch(&setup_params, &batch).is_ok());
+
+        proofs[1].committed_ballot = proofs[0].committed_ballot;
+        let batch: Vec<(&ElectionContext, &RankedVotingProof)> = contexts.iter().zip(&proofs).collect();
+        assert_eq!(verify_batch(&setup_params, &batch).unwrap_err().invalid, vec![(1, VerificationError::PermutationProofFailed)]);
+
+        let batch = [(&contexts[0], &proofs[0]), (&contexts[1], &proofs[3])];
+        assert_eq!(verify_batch(&setup_params, &batch).unwrap_err().invalid, vec![(1, VerificationError::PermutationProofFailed)]);
+    }
+
+    #[test]
+    fn test_proof_is_bound_to_context_and_setup() {
+        let setup_params = setup(4, ELECTION_ID).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), &[3, 1, 0, 2]).unwrap();
+
+        for context in [
+            ElectionContext::new(b"test election", b"other contest", b"test voter"),
+            ElectionContext::new(b"test election", b"test contest", b"other voter"),
+        ] {
+            assert_eq!(verify_proof(&setup_params, &context, &proof), Err(VerificationError::PermutationProofFailed));
+        }
+
+        let other = setup_with_scores(vec![3, 2, 1, 1], ELECTION_ID).unwrap();
+        assert_eq!(verify_proof(&other, &test_context(), &proof), Err(VerificationError::PermutationProofFailed));
+    }
 }
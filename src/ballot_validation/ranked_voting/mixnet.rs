@@ -0,0 +1,662 @@
+use std::collections::HashMap;
+use ark_bls12_381::{Fr, G1Affine, G1Projective};
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{PrimeField, Zero};
+use rand::{rngs::OsRng, seq::SliceRandom, CryptoRng, RngCore};
+use zeroize::Zeroize;
+use curdleproofs::curdleproofs::{CurdleproofsCrs, CurdleproofsProof};
+use curdleproofs::transcript::CurdleproofsTranscript;
+use curdleproofs::util::{generate_blinders, shuffle_permute_and_commit_input};
+use curdleproofs::N_BLINDERS;
+use crate::ballot_validation::election_context::ElectionContext;
+use crate::ballot_validation::encoding::{impl_serde_via_bytes, ObjectKind, Reader, SchemeTag, Writer};
+use crate::ballot_validation::errors::{BallotError, DecodingError, VerificationError};
+use super::{find_permutation, hash_to_g1, sum_affine_points, SetupParameters};
+
+/// Largest ranking size the mixnet accepts. Decrypting an entry builds a
+/// baby-step table of about `sqrt(n!)` points: about 200 for 8 candidates,
+/// but already 4.6 million for 16, the next ranked ballot size.
+const MAX_RANKING_SIZE: usize = 8;
+
+/// Shuffle parameters for a list of `capacity` entries. The Curdleproofs CRS
+/// is hashed to the curve from the election identifier, like the ranked
+/// voting generators.
+pub struct MixParameters {
+    crs: CurdleproofsCrs,
+    capacity: usize,
+}
+
+/// Decryption key of the mixnet, zeroed on drop.
+pub struct MixSecretKey {
+    scalar: Fr,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MixPublicKey {
+    point: G1Affine,
+}
+
+/// A ranking `m` encrypted as `(ρ * G, ρ * (Y + m * G))`. A Curdleproofs
+/// shuffle multiplies every entry by the same secret scalar, which maps this
+/// form onto itself with a fresh `ρ`, so entries stay decryptable after any
+/// number of mixes. `m = 0` marks padding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncryptedRanking {
+    r: G1Affine,
+    s: G1Affine,
+}
+
+/// An encrypted ranking as cast by a voter, with a Schnorr proof of knowledge
+/// of its `ρ` bound to the voter's election context. Without it anyone could
+/// re-randomize another voter's entry and cast the copy as their own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CastRanking {
+    ranking: EncryptedRanking,
+    nonce_commitment: G1Affine,
+    response: Fr,
+}
+
+/// One mix server's output and its shuffle proof.
+///
+/// Curdleproofs 0.0.1 keeps the fields of its shuffle proof private and
+/// implements no encoding for it, so a `Mix` has no wire format yet. Its
+/// outputs can be published entry by entry with [`EncryptedRanking::to_bytes`].
+#[derive(Clone)]
+pub struct Mix {
+    outputs: Vec<EncryptedRanking>,
+    permutation_commitment: G1Projective,
+    proof: CurdleproofsProof,
+}
+
+impl_serde_via_bytes!(MixPublicKey, "mix public key");
+impl_serde_via_bytes!(EncryptedRanking, "encrypted ranking");
+impl_serde_via_bytes!(CastRanking, "cast ranking");
+
+/// `capacity + N_BLINDERS` must be a power of two, as required by the
+/// Curdleproofs inner product argument. See [`capacity_for`].
+pub fn setup(capacity: usize, election_id: &[u8]) -> Result<MixParameters, BallotError> {
+    if capacity == 0 || !(capacity + N_BLINDERS).is_power_of_two() {
+        return Err(BallotError::InvalidSetup(format!("mix capacity plus {} must be a power of two", N_BLINDERS)));
+    }
+
+    let vec_g: Vec<G1Affine> = (0..capacity as u64).map(|index| hash_to_g1(election_id, b"mix g", index)).collect();
+    let vec_h: Vec<G1Affine> = (0..N_BLINDERS as u64).map(|index| hash_to_g1(election_id, b"mix h", index)).collect();
+
+    Ok(MixParameters {
+        crs: CurdleproofsCrs {
+            G_sum: sum_affine_points(&vec_g),
+            H_sum: sum_affine_points(&vec_h),
+            vec_G: vec_g,
+            vec_H: vec_h,
+            H: hash_to_g1(election_id, b"mix H", 0).into_projective(),
+            G_t: hash_to_g1(election_id, b"mix G_t", 0).into_projective(),
+            G_u: hash_to_g1(election_id, b"mix G_u", 0).into_projective(),
+        },
+        capacity,
+    })
+}
+
+/// Smallest valid capacity that holds `ballots` entries.
+pub fn capacity_for(ballots: usize) -> usize {
+    (ballots.max(1) + N_BLINDERS).next_power_of_two() - N_BLINDERS
+}
+
+impl MixParameters {
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl MixSecretKey {
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> MixSecretKey {
+        MixSecretKey { scalar: nonzero_scalar(rng) }
+    }
+
+    pub fn public_key(&self) -> MixPublicKey {
+        MixPublicKey { point: G1Affine::prime_subgroup_generator().mul(self.scalar.into_repr()).into_affine() }
+    }
+
+    /// Decrypts a mixed entry back to its ballot, or `None` for padding.
+    /// Recovering the permutation index is a baby-step giant-step search over
+    /// the `n!` rankings, so it costs about `sqrt(n!)` group operations.
+    pub fn decrypt(&self, ballot_params: &SetupParameters, entry: &EncryptedRanking) -> Result<Option<Vec<u32>>, VerificationError> {
+        // s - x * r = m * r
+        let masked: G1Projective = entry.s.into_projective() - entry.r.mul(self.scalar.into_repr());
+        if masked.is_zero() {
+            return Ok(None);
+        }
+
+        let size: usize = ballot_params.ballot_size;
+        if size > MAX_RANKING_SIZE {
+            return Err(VerificationError::UndecodableBallot);
+        }
+        let rankings: u64 = factorial(size).ok_or(VerificationError::UndecodableBallot)?;
+        let index: u64 = solve_multiple(&entry.r, &masked, rankings).ok_or(VerificationError::UndecodableBallot)?;
+        let permutation: Vec<u32> = permutation_from_rank(index - 1, size);
+
+        Ok(Some(permutation.iter().map(|&i| ballot_params.a.scores[i as usize]).collect()))
+    }
+}
+
+impl Drop for MixSecretKey {
+    fn drop(&mut self) {
+        self.scalar.zeroize();
+    }
+}
+
+impl MixPublicKey {
+    pub fn from_point(point: G1Affine) -> MixPublicKey {
+        MixPublicKey { point }
+    }
+
+    pub fn point(&self) -> &G1Affine {
+        &self.point
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::Mixnet, ObjectKind::PublicKey);
+        writer.g1_point(&self.point);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<MixPublicKey, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::Mixnet, ObjectKind::PublicKey)?;
+        let point = reader.g1_point()?;
+        reader.finish()?;
+
+        Ok(MixPublicKey { point })
+    }
+
+    /// The encryption of `m = 0` filling slot `index`, with `ρ = index + 1`
+    /// so anyone can recompute it. A shuffle scales every entry by the same
+    /// scalar, so padding slots need distinct randomizers to stay unlinkable.
+    pub fn padding(&self, index: usize) -> EncryptedRanking {
+        let randomizer: Fr = Fr::from(index as u64 + 1);
+        EncryptedRanking {
+            r: G1Affine::prime_subgroup_generator().mul(randomizer.into_repr()).into_affine(),
+            s: self.point.mul(randomizer.into_repr()).into_affine(),
+        }
+    }
+}
+
+impl EncryptedRanking {
+    pub fn r(&self) -> &G1Affine {
+        &self.r
+    }
+
+    pub fn s(&self) -> &G1Affine {
+        &self.s
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::Mixnet, ObjectKind::EncryptedRanking);
+        writer.g1_point(&self.r).g1_point(&self.s);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<EncryptedRanking, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::Mixnet, ObjectKind::EncryptedRanking)?;
+        let (r, s) = (reader.g1_point()?, reader.g1_point()?);
+        reader.finish()?;
+
+        Ok(EncryptedRanking { r, s })
+    }
+}
+
+impl CastRanking {
+    pub fn ranking(&self) -> &EncryptedRanking {
+        &self.ranking
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::Mixnet, ObjectKind::CastRanking);
+        writer
+            .g1_point(&self.ranking.r)
+            .g1_point(&self.ranking.s)
+            .g1_point(&self.nonce_commitment)
+            .fr(&self.response);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<CastRanking, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::Mixnet, ObjectKind::CastRanking)?;
+        let ranking = EncryptedRanking { r: reader.g1_point()?, s: reader.g1_point()? };
+        let nonce_commitment = reader.g1_point()?;
+        let response = reader.fr()?;
+        reader.finish()?;
+
+        Ok(CastRanking { ranking, nonce_commitment, response })
+    }
+
+    pub fn verify(&self, public_key: &MixPublicKey, context: &ElectionContext) -> bool {
+        let challenge: Fr = randomizer_challenge(public_key, context, &self.ranking, &self.nonce_commitment);
+        G1Affine::prime_subgroup_generator().mul(self.response.into_repr())
+            == self.nonce_commitment.into_projective() + self.ranking.r.mul(challenge.into_repr())
+    }
+}
+
+impl Mix {
+    pub fn outputs(&self) -> &[EncryptedRanking] {
+        &self.outputs
+    }
+}
+
+/// Encrypts a ranked ballot, given as scores like for `generate_vote`.
+pub fn encrypt_ranking<R: RngCore + CryptoRng>(
+    ballot_params: &SetupParameters,
+    public_key: &MixPublicKey,
+    context: &ElectionContext,
+    scores: &[u32],
+    rng: &mut R,
+) -> Result<CastRanking, BallotError> {
+    if scores.len() > MAX_RANKING_SIZE {
+        return Err(BallotError::InvalidSetup(format!("mixed rankings are limited to {} candidates", MAX_RANKING_SIZE)));
+    }
+    let permutation: Vec<u32> = find_permutation(&ballot_params.a.scores, scores)?;
+    let message: Fr = Fr::from(permutation_rank(&permutation) + 1);
+
+    let generator = G1Affine::prime_subgroup_generator();
+    let randomizer: Fr = nonzero_scalar(rng);
+    let masked: G1Projective = public_key.point.into_projective() + generator.mul(message.into_repr());
+
+    let ranking = EncryptedRanking {
+        r: generator.mul(randomizer.into_repr()).into_affine(),
+        s: masked.mul(randomizer.into_repr()).into_affine(),
+    };
+
+    let nonce: Fr = nonzero_scalar(rng);
+    let nonce_commitment: G1Affine = generator.mul(nonce.into_repr()).into_affine();
+    let challenge: Fr = randomizer_challenge(public_key, context, &ranking, &nonce_commitment);
+
+    Ok(CastRanking { ranking, nonce_commitment, response: nonce + challenge * randomizer })
+}
+
+/// Checks every cast ranking against its voter's context and fills the list
+/// up to the mix capacity with padding, giving the first server's input.
+pub fn mix_inputs(
+    params: &MixParameters,
+    public_key: &MixPublicKey,
+    ballots: &[(&ElectionContext, &CastRanking)],
+) -> Result<Vec<EncryptedRanking>, VerificationError> {
+    if ballots.len() > params.capacity {
+        return Err(VerificationError::LengthMismatch { expected: params.capacity, actual: ballots.len() });
+    }
+
+    let mut inputs: Vec<EncryptedRanking> = Vec::with_capacity(params.capacity);
+    for (ballot, (context, cast)) in ballots.iter().enumerate() {
+        if !cast.verify(public_key, context) {
+            return Err(VerificationError::RandomizerProofFailed { ballot });
+        }
+        inputs.push(cast.ranking);
+    }
+    inputs.extend((ballots.len()..params.capacity).map(|index| public_key.padding(index)));
+
+    Ok(inputs)
+}
+
+/// Re-randomizes and shuffles `inputs` with a secret permutation and scalar.
+pub fn mix<R: RngCore + CryptoRng>(params: &MixParameters, inputs: &[EncryptedRanking], rng: &mut R) -> Result<Mix, BallotError> {
+    if inputs.len() != params.capacity {
+        return Err(BallotError::LengthMismatch { expected: params.capacity, actual: inputs.len() });
+    }
+
+    let mut permutation: Vec<u32> = (0..params.capacity as u32).collect();
+    permutation.shuffle(rng);
+    let randomizer: Fr = nonzero_scalar(rng);
+
+    let (vec_r, vec_s) = split(inputs);
+    let (vec_t, vec_u, permutation_commitment, blinders) =
+        shuffle_permute_and_commit_input(&params.crs, &vec_r, &vec_s, &permutation, &randomizer, rng);
+
+    let outputs: Vec<EncryptedRanking> = vec_t.iter().zip(&vec_u).map(|(&r, &s)| EncryptedRanking { r, s }).collect();
+    let proof = CurdleproofsProof::new(
+        &params.crs,
+        vec_r,
+        vec_s,
+        vec_t,
+        vec_u,
+        permutation_commitment,
+        permutation,
+        randomizer,
+        blinders,
+        rng,
+    );
+
+    Ok(Mix { outputs, permutation_commitment, proof })
+}
+
+/// Verifies a chain of mixes, where each server shuffles the previous
+/// server's outputs. The first server's input is rebuilt from the cast
+/// rankings with [`mix_inputs`], so it must hold exactly those ballots and
+/// the public padding. Returns the final list, ready for decryption.
+pub fn verify_mix_chain(
+    params: &MixParameters,
+    public_key: &MixPublicKey,
+    ballots: &[(&ElectionContext, &CastRanking)],
+    mixes: &[Mix],
+) -> Result<Vec<EncryptedRanking>, VerificationError> {
+    let inputs: Vec<EncryptedRanking> = mix_inputs(params, public_key, ballots)?;
+    let mut current: &[EncryptedRanking] = &inputs;
+
+    for (server, step) in mixes.iter().enumerate() {
+        for len in [current.len(), step.outputs.len()] {
+            if len != params.capacity {
+                return Err(VerificationError::LengthMismatch { expected: params.capacity, actual: len });
+            }
+        }
+
+        let (vec_r, vec_s) = split(current);
+        let (vec_t, vec_u) = split(&step.outputs);
+        step.proof
+            .verify(&params.crs, &vec_r, &vec_s, &vec_t, &vec_u, &step.permutation_commitment, &mut OsRng)
+            .map_err(|_| VerificationError::ShuffleProofFailed { server })?;
+
+        current = &step.outputs;
+    }
+
+    Ok(current.to_vec())
+}
+
+fn randomizer_challenge(
+    public_key: &MixPublicKey,
+    context: &ElectionContext,
+    ranking: &EncryptedRanking,
+    nonce_commitment: &G1Affine,
+) -> Fr {
+    let mut transcript = context.transcript(b"mixnet ranking");
+    transcript.append(b"public key", &public_key.point);
+    transcript.append(b"r", &ranking.r);
+    transcript.append(b"s", &ranking.s);
+    transcript.append(b"nonce", nonce_commitment);
+    transcript.get_and_append_challenge(b"challenge")
+}
+
+fn split(entries: &[EncryptedRanking]) -> (Vec<G1Affine>, Vec<G1Affine>) {
+    entries.iter().map(|entry| (entry.r, entry.s)).unzip()
+}
+
+fn nonzero_scalar<R: RngCore + CryptoRng>(rng: &mut R) -> Fr {
+    loop {
+        let scalar = generate_blinders(rng, 1)[0];
+        if !scalar.is_zero() {
+            return scalar;
+        }
+    }
+}
+
+fn factorial(n: usize) -> Option<u64> {
+    (1..=n as u64).try_fold(1u64, |acc, i| acc.checked_mul(i))
+}
+
+/// Lexicographic index of a permutation of `0..n` (its Lehmer code).
+fn permutation_rank(permutation: &[u32]) -> u64 {
+    let n = permutation.len();
+    permutation.iter().enumerate().fold(0u64, |rank, (i, &p)| {
+        let smaller_later = permutation[i + 1..].iter().filter(|&&q| q < p).count() as u64;
+        rank * (n - i) as u64 + smaller_later
+    })
+}
+
+fn permutation_from_rank(mut rank: u64, n: usize) -> Vec<u32> {
+    let mut digits = vec![0u64; n];
+    for i in (0..n).rev() {
+        let base = (n - i) as u64;
+        digits[i] = rank % base;
+        rank /= base;
+    }
+
+    let mut available: Vec<u32> = (0..n as u32).collect();
+    digits.iter().map(|&d| available.remove(d as usize)).collect()
+}
+
+/// Finds `m` in `[1, max]` with `target = m * base`.
+fn solve_multiple(base: &G1Affine, target: &G1Projective, max: u64) -> Option<u64> {
+    let mut step_count: u64 = (max as f64).sqrt() as u64;
+    while step_count * step_count <= max {
+        step_count += 1;
+    }
+
+    let mut baby_steps: Vec<G1Projective> = Vec::with_capacity(step_count as usize);
+    let mut point = G1Projective::zero();
+    for _ in 0..step_count {
+        baby_steps.push(point);
+        point.add_assign_mixed(base);
+    }
+    G1Projective::batch_normalization(&mut baby_steps);
+    let table: HashMap<G1Affine, u64> = baby_steps.into_iter().map(|p| p.into_affine()).zip(0..).collect();
+
+    let giant_step: G1Projective = base.mul(Fr::from(step_count).into_repr());
+    let mut current: G1Projective = *target;
+    for i in 0..=max / step_count {
+        if let Some(&j) = table.get(&current.into_affine()) {
+            let m = i * step_count + j;
+            if (1..=max).contains(&m) {
+                return Some(m);
+            }
+        }
+        current -= giant_step;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381::Fq;
+    use ark_serialize::CanonicalSerialize;
+    use rand::thread_rng;
+    use crate::ballot_validation::election_context::test_context;
+    use crate::ballot_validation::ranked_voting::{setup as ranked_setup, setup_truncated};
+
+    const ELECTION_ID: &[u8] = b"test election";
+
+    fn voter(id: u8) -> ElectionContext {
+        ElectionContext::new(ELECTION_ID, b"test contest", &[id])
+    }
+
+    fn run_chain(params: &MixParameters, inputs: &[EncryptedRanking], servers: usize) -> Vec<Mix> {
+        let mut mixes: Vec<Mix> = Vec::new();
+        for _ in 0..servers {
+            let current = mixes.last().map_or(inputs, |m| m.outputs());
+            let next = mix(params, current, &mut thread_rng()).unwrap();
+            mixes.push(next);
+        }
+        mixes
+    }
+
+    #[test]
+    fn test_mix_chain_preserves_ballots() {
+        let ballot_params = ranked_setup(4, ELECTION_ID).unwrap();
+        let secret_key = MixSecretKey::generate(&mut thread_rng());
+        let public_key = secret_key.public_key();
+        let ballots: Vec<Vec<u32>> = vec![vec![3, 2, 1, 0], vec![0, 1, 2, 3], vec![1, 3, 0, 2], vec![3, 2, 1, 0]];
+
+        let params = setup(capacity_for(ballots.len() + 1), ELECTION_ID).unwrap();
+        let contexts: Vec<ElectionContext> = (0..ballots.len() as u8).map(voter).collect();
+        let cast: Vec<CastRanking> = ballots
+            .iter()
+            .zip(&contexts)
+            .map(|(ballot, context)| encrypt_ranking(&ballot_params, &public_key, context, ballot, &mut thread_rng()).unwrap())
+            .collect();
+        let submitted: Vec<(&ElectionContext, &CastRanking)> = contexts.iter().zip(&cast).collect();
+        let inputs = mix_inputs(&params, &public_key, &submitted).unwrap();
+
+        let mixes = run_chain(&params, &inputs, 3);
+        let outputs = verify_mix_chain(&params, &public_key, &submitted, &mixes).unwrap();
+
+        let mut decrypted: Vec<Vec<u32>> = outputs
+            .iter()
+            .filter_map(|entry| secret_key.decrypt(&ballot_params, entry).unwrap())
+            .collect();
+        let mut expected = ballots.clone();
+        decrypted.sort();
+        expected.sort();
+        assert_eq!(decrypted, expected);
+    }
+
+    #[test]
+    fn test_outputs_are_rerandomized() {
+        let public_key = MixSecretKey::generate(&mut thread_rng()).public_key();
+        let params = setup(4, ELECTION_ID).unwrap();
+        let inputs = mix_inputs(&params, &public_key, &[]).unwrap();
+
+        // Padding slots use distinct randomizers, so they cannot be told
+        // apart or matched to their inputs after the shuffle.
+        let mixed = mix(&params, &inputs, &mut thread_rng()).unwrap();
+        for (i, output) in mixed.outputs().iter().enumerate() {
+            assert!(!inputs.contains(output));
+            assert!(!mixed.outputs()[..i].contains(output));
+        }
+    }
+
+    #[test]
+    fn test_tampered_mix_is_rejected() {
+        let ballot_params = ranked_setup(4, ELECTION_ID).unwrap();
+        let public_key = MixSecretKey::generate(&mut thread_rng()).public_key();
+        let params = setup(4, ELECTION_ID).unwrap();
+        let ballot = encrypt_ranking(&ballot_params, &public_key, &test_context(), &[3, 2, 1, 0], &mut thread_rng()).unwrap();
+        let submitted = [(&test_context(), &ballot)];
+        let inputs = mix_inputs(&params, &public_key, &submitted).unwrap();
+
+        // The second server replaces an entry with a ballot of its own.
+        let mut mixes = run_chain(&params, &inputs, 2);
+        mixes[1].outputs[0] = *encrypt_ranking(&ballot_params, &public_key, &test_context(), &[0, 1, 2, 3], &mut thread_rng()).unwrap().ranking();
+        assert_eq!(verify_mix_chain(&params, &public_key, &submitted, &mixes).err(), Some(VerificationError::ShuffleProofFailed { server: 1 }));
+
+        // A mix of some other list does not extend the chain.
+        let unrelated = mix(&params, &inputs, &mut thread_rng()).unwrap();
+        let mut mixes = run_chain(&params, &inputs, 1);
+        mixes.push(unrelated);
+        assert_eq!(verify_mix_chain(&params, &public_key, &submitted, &mixes).err(), Some(VerificationError::ShuffleProofFailed { server: 1 }));
+
+        // The first server cannot slip in a ballot disguised as padding.
+        let mut forged_inputs = inputs.clone();
+        forged_inputs[3] = *encrypt_ranking(&ballot_params, &public_key, &test_context(), &[0, 1, 2, 3], &mut thread_rng()).unwrap().ranking();
+        let mixes = run_chain(&params, &forged_inputs, 1);
+        assert_eq!(verify_mix_chain(&params, &public_key, &submitted, &mixes).err(), Some(VerificationError::ShuffleProofFailed { server: 0 }));
+    }
+
+    #[test]
+    fn test_copied_ranking_is_rejected() {
+        let ballot_params = ranked_setup(4, ELECTION_ID).unwrap();
+        let public_key = MixSecretKey::generate(&mut thread_rng()).public_key();
+        let params = setup(4, ELECTION_ID).unwrap();
+        let (alice, mallory) = (voter(0), voter(1));
+        let cast = encrypt_ranking(&ballot_params, &public_key, &alice, &[3, 2, 1, 0], &mut thread_rng()).unwrap();
+
+        // Replaying the ballot under another voter's context fails.
+        assert_eq!(
+            mix_inputs(&params, &public_key, &[(&alice, &cast), (&mallory, &cast)]).err(),
+            Some(VerificationError::RandomizerProofFailed { ballot: 1 })
+        );
+
+        // So does re-randomizing it, since the copier does not know the new randomizer.
+        let k: Fr = nonzero_scalar(&mut thread_rng());
+        let mut copy = cast;
+        copy.ranking = EncryptedRanking { r: cast.ranking.r.mul(k.into_repr()).into_affine(), s: cast.ranking.s.mul(k.into_repr()).into_affine() };
+        assert!(!copy.verify(&public_key, &alice));
+        assert_eq!(
+            mix_inputs(&params, &public_key, &[(&alice, &cast), (&mallory, &copy)]).err(),
+            Some(VerificationError::RandomizerProofFailed { ballot: 1 })
+        );
+
+        assert!(cast.verify(&public_key, &alice));
+    }
+
+    #[test]
+    fn test_truncated_rankings_round_trip() {
+        let ballot_params = setup_truncated(8, 3, ELECTION_ID).unwrap();
+        let secret_key = MixSecretKey::generate(&mut thread_rng());
+        let ballot = vec![0, 2, 0, 0, 1, 0, 0, 0];
+
+        let entry = *encrypt_ranking(&ballot_params, &secret_key.public_key(), &test_context(), &ballot, &mut thread_rng()).unwrap().ranking();
+        assert_eq!(secret_key.decrypt(&ballot_params, &entry), Ok(Some(ballot)));
+        assert_eq!(secret_key.decrypt(&ballot_params, &secret_key.public_key().padding(0)), Ok(None));
+
+        let other_key = MixSecretKey::generate(&mut thread_rng());
+        assert_eq!(other_key.decrypt(&ballot_params, &entry), Err(VerificationError::UndecodableBallot));
+        assert!(encrypt_ranking(&ballot_params, &secret_key.public_key(), &test_context(), &[2, 2, 0, 0, 1, 0, 0, 0], &mut thread_rng()).is_err());
+    }
+
+    #[test]
+    fn test_ranking_size_limit() {
+        let secret_key = MixSecretKey::generate(&mut thread_rng());
+        let largest = ranked_setup(MAX_RANKING_SIZE, ELECTION_ID).unwrap();
+        let ballot: Vec<u32> = (0..MAX_RANKING_SIZE as u32).collect();
+
+        let entry = *encrypt_ranking(&largest, &secret_key.public_key(), &test_context(), &ballot, &mut thread_rng()).unwrap().ranking();
+        assert_eq!(secret_key.decrypt(&largest, &entry), Ok(Some(ballot)));
+
+        let too_large = ranked_setup(2 * MAX_RANKING_SIZE, ELECTION_ID).unwrap();
+        let ballot: Vec<u32> = (0..2 * MAX_RANKING_SIZE as u32).collect();
+        assert!(encrypt_ranking(&too_large, &secret_key.public_key(), &test_context(), &ballot, &mut thread_rng()).is_err());
+        assert_eq!(secret_key.decrypt(&too_large, &entry), Err(VerificationError::UndecodableBallot));
+    }
+
+    #[test]
+    fn test_encoding_round_trip() {
+        let ballot_params = ranked_setup(4, ELECTION_ID).unwrap();
+        let public_key = MixSecretKey::generate(&mut thread_rng()).public_key();
+        let cast = encrypt_ranking(&ballot_params, &public_key, &test_context(), &[3, 2, 1, 0], &mut thread_rng()).unwrap();
+
+        let decoded_key = MixPublicKey::from_bytes(&public_key.to_bytes()).unwrap();
+        let decoded = CastRanking::from_bytes(&cast.to_bytes()).unwrap();
+        assert_eq!(decoded, cast);
+        assert!(decoded.verify(&decoded_key, &test_context()));
+        assert_eq!(EncryptedRanking::from_bytes(&cast.ranking().to_bytes()), Ok(*cast.ranking()));
+
+        let deserialized: CastRanking = bincode::deserialize(&bincode::serialize(&cast).unwrap()).unwrap();
+        assert_eq!(deserialized, cast);
+
+        assert_eq!(CastRanking::from_bytes(&cast.ranking().to_bytes()).err(), Some(DecodingError::UnexpectedTag));
+        let bytes = cast.to_bytes();
+        assert_eq!(CastRanking::from_bytes(&bytes[..bytes.len() - 1]).err(), Some(DecodingError::UnexpectedEnd));
+    }
+
+    #[test]
+    fn test_points_outside_the_subgroup_are_rejected() {
+        let public_key = MixSecretKey::generate(&mut thread_rng()).public_key();
+
+        // A point on the curve but outside the prime-order subgroup.
+        let outside: G1Affine = (1u64..)
+            .filter_map(|x| G1Affine::get_point_from_x(Fq::from(x), true))
+            .find(|point| !point.is_in_correct_subgroup_assuming_on_curve())
+            .unwrap();
+        let mut bytes = public_key.padding(0).to_bytes();
+        let mut encoded = Vec::new();
+        outside.serialize(&mut encoded).unwrap();
+        bytes[3..3 + encoded.len()].copy_from_slice(&encoded);
+        assert_eq!(EncryptedRanking::from_bytes(&bytes).err(), Some(DecodingError::InvalidPoint));
+
+        let mut infinity = Vec::new();
+        G1Affine::zero().serialize(&mut infinity).unwrap();
+        let mut bytes = public_key.to_bytes();
+        bytes[3..].copy_from_slice(&infinity);
+        assert_eq!(MixPublicKey::from_bytes(&bytes).err(), Some(DecodingError::InvalidPoint));
+    }
+
+    #[test]
+    fn test_capacity() {
+        assert_eq!([1, 4, 5, 12, 13].map(capacity_for), [4, 4, 12, 12, 28]);
+        assert!(setup(5, ELECTION_ID).is_err());
+        assert!(setup(0, ELECTION_ID).is_err());
+
+        let params = setup(4, ELECTION_ID).unwrap();
+        let public_key = MixSecretKey::generate(&mut thread_rng()).public_key();
+        let ballot_params = ranked_setup(4, ELECTION_ID).unwrap();
+        let cast = encrypt_ranking(&ballot_params, &public_key, &test_context(), &[3, 2, 1, 0], &mut thread_rng()).unwrap();
+        let context = test_context();
+        assert_eq!(mix_inputs(&params, &public_key, &[(&context, &cast); 5]).err(), Some(VerificationError::LengthMismatch { expected: 4, actual: 5 }));
+        assert_eq!(mix(&params, &[public_key.padding(0); 3], &mut thread_rng()).err(), Some(BallotError::LengthMismatch { expected: 4, actual: 3 }));
+    }
+
+    #[test]
+    fn test_permutation_rank_round_trip() {
+        for rank in 0..24 {
+            assert_eq!(permutation_rank(&permutation_from_rank(rank, 4)), rank);
+        }
+        assert_eq!(permutation_rank(&[0, 1, 2, 3]), 0);
+        assert_eq!(permutation_rank(&[3, 2, 1, 0]), 23);
+    }
+}
@@ -0,0 +1,368 @@
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+use curve25519_dalek_ng::{ristretto::CompressedRistretto, scalar::Scalar};
+use bulletproofs::{BulletproofGens, PedersenGens};
+use bulletproofs::r1cs::{ConstraintSystem, Prover, R1CSError, R1CSProof, RandomizableConstraintSystem, RandomizedConstraintSystem, Variable, Verifier};
+use crate::ballot_validation::ballot_scheme::{verify_each, BallotScheme};
+use crate::ballot_validation::election_context::ElectionContext;
+use crate::ballot_validation::encoding::{impl_serde_via_bytes, ObjectKind, Reader, SchemeTag, Writer};
+use crate::ballot_validation::errors::{BallotError, BatchVerificationError, DecodingError, VerificationError};
+
+/// Setup for ranked ballots with ties.
+///
+/// Ties are scored with the minimum-score rule: every candidate scores the
+/// number of candidates ranked strictly below it. Without ties this is the
+/// Borda count; a group of tied candidates all receive the lowest Borda score
+/// of the positions they occupy. For four candidates ranked `A > B = C > D`
+/// the scores are `A = 3, B = 1, C = 1, D = 0`.
+pub struct SetupParameters {
+    pc_gens: PedersenGens,
+    bp_gens: BulletproofGens,
+    ballot_size: usize,
+    encoding: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct WeakOrderingProof {
+    score_commitments: Vec<CompressedRistretto>,
+    r1cs_proof: R1CSProof,
+}
+
+impl SetupParameters {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encoding.clone()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::WeakOrderingRankedVoting, ObjectKind::SetupParameters);
+        writer
+            .pc_gens(&self.pc_gens)
+            .u64(self.ballot_size as u64);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SetupParameters, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::WeakOrderingRankedVoting, ObjectKind::SetupParameters)?;
+        let pc_gens = reader.pc_gens()?;
+        let ballot_size = reader.usize()?;
+        reader.finish()?;
+
+        setup(ballot_size, Some(pc_gens)).map_err(DecodingError::InvalidParameters)
+    }
+}
+
+impl WeakOrderingProof {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::WeakOrderingRankedVoting, ObjectKind::Proof);
+        writer
+            .points(&self.score_commitments)
+            .r1cs_proof(&self.r1cs_proof);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<WeakOrderingProof, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::WeakOrderingRankedVoting, ObjectKind::Proof)?;
+        let score_commitments = reader.points()?;
+        let r1cs_proof = reader.r1cs_proof()?;
+        reader.finish()?;
+
+        Ok(WeakOrderingProof {
+            score_commitments,
+            r1cs_proof
+        })
+    }
+}
+
+impl_serde_via_bytes!(SetupParameters, "weak ordering ranked voting setup");
+impl_serde_via_bytes!(WeakOrderingProof, "weak ordering ranked voting proof");
+
+pub struct WeakOrderingRankedVoting;
+
+impl BallotScheme for WeakOrderingRankedVoting {
+    type Params = SetupParameters;
+    type Ballot = [u32];
+    type Proof = WeakOrderingProof;
+    type Error = BallotError;
+
+    fn generate_vote_with_rng<R: RngCore + CryptoRng>(params: &SetupParameters, context: &ElectionContext, ballot: &[u32], rng: &mut R) -> Result<WeakOrderingProof, BallotError> {
+        generate_vote_with_rng(params, context, ballot, rng)
+    }
+
+    fn verify_proof(params: &SetupParameters, context: &ElectionContext, proof: &WeakOrderingProof) -> Result<(), VerificationError> {
+        verify_proof(params, context, proof)
+    }
+
+    fn verify_batch(params: &SetupParameters, ballots: &[(&ElectionContext, &WeakOrderingProof)]) -> Result<(), BatchVerificationError> {
+        verify_batch(params, ballots)
+    }
+}
+
+pub fn setup(ballot_size: usize, pc_gens: Option<PedersenGens>) -> Result<SetupParameters, BallotError> {
+    if ballot_size == 0 {
+        return Err(BallotError::InvalidSetup("ballot_size must be greater than zero".into()));
+    }
+
+    // Two multiplier chains for the shuffle and one tie constraint per position.
+    let multipliers: usize = ballot_size
+        .checked_mul(3)
+        .ok_or_else(|| BallotError::InvalidSetup("ballot_size is too large".into()))?;
+    let pc_gens: PedersenGens = pc_gens.unwrap_or_default();
+    let bp_gens: BulletproofGens = BulletproofGens::new(multipliers.next_power_of_two(), 1);
+
+    let mut setup_params = SetupParameters {
+        pc_gens,
+        bp_gens,
+        ballot_size,
+        encoding: Vec::new(),
+    };
+    setup_params.encoding = setup_params.encode();
+
+    Ok(setup_params)
+}
+
+/// Converts rank levels (`0` is most preferred, equal levels are tied) into
+/// the scores `generate_vote` expects.
+pub fn scores_from_ranks(ranks: &[u32]) -> Vec<u32> {
+    ranks
+        .iter()
+        .map(|&rank| ranks.iter().filter(|&&other| other > rank).count() as u32)
+        .collect()
+}
+
+/// Proves that `scores` is a valid tied Borda assignment, see [`SetupParameters`].
+pub fn generate_vote(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    scores: &[u32],
+) -> Result<WeakOrderingProof, BallotError> {
+    generate_vote_with_rng(setup_params, context, scores, &mut OsRng)
+}
+
+pub fn generate_vote_with_rng<R: RngCore + CryptoRng>(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    scores: &[u32],
+    rng: &mut R,
+) -> Result<WeakOrderingProof, BallotError> {
+    if scores.len() != setup_params.ballot_size {
+        return Err(BallotError::LengthMismatch { expected: setup_params.ballot_size, actual: scores.len() });
+    }
+
+    if !is_weak_ordering(&sorted(scores)) {
+        return Err(BallotError::InvalidPermutation("scores are not a tied Borda assignment".into()));
+    }
+
+    prove_scores(setup_params, context, scores, rng)
+}
+
+pub fn verify_proof(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    validity_proof: &WeakOrderingProof,
+) -> Result<(), VerificationError> {
+    if validity_proof.score_commitments.len() != setup_params.ballot_size {
+        return Err(VerificationError::LengthMismatch { expected: setup_params.ballot_size, actual: validity_proof.score_commitments.len() });
+    }
+
+    let mut transcript = context.scheme_transcript(b"weak ordering ranked voting", &setup_params.encoding);
+    let mut verifier = Verifier::new(&mut transcript);
+
+    let score_variables: Vec<Variable> = validity_proof
+        .score_commitments
+        .iter()
+        .map(|commitment| verifier.commit(*commitment))
+        .collect();
+
+    weak_ordering_constraints(&mut verifier, score_variables, None)
+        .map_err(VerificationError::ConstraintProofFailed)?;
+
+    verifier
+        .verify(&validity_proof.r1cs_proof, &setup_params.pc_gens, &setup_params.bp_gens)
+        .map_err(VerificationError::ConstraintProofFailed)
+}
+
+/// A weak ordering ballot is a single R1CS proof, so the ballots are
+/// verified individually.
+pub fn verify_batch(
+    setup_params: &SetupParameters,
+    ballots: &[(&ElectionContext, &WeakOrderingProof)],
+) -> Result<(), BatchVerificationError> {
+    verify_each(ballots, |context, proof| verify_proof(setup_params, context, proof))
+}
+
+fn prove_scores<R: RngCore + CryptoRng>(setup_params: &SetupParameters, context: &ElectionContext, scores: &[u32], rng: &mut R) -> Result<WeakOrderingProof, BallotError> {
+    let mut transcript = context.scheme_transcript(b"weak ordering ranked voting", &setup_params.encoding);
+    let mut prover = Prover::new(&setup_params.pc_gens, &mut transcript);
+
+    let (score_commitments, score_variables): (Vec<CompressedRistretto>, Vec<Variable>) = scores
+        .iter()
+        .map(|&score| prover.commit(Scalar::from(score), Scalar::random(rng)))
+        .unzip();
+
+    weak_ordering_constraints(&mut prover, score_variables, Some(sorted(scores)))
+        .map_err(BallotError::ConstraintProofFailed)?;
+
+    let r1cs_proof = prover
+        .prove(&setup_params.bp_gens)
+        .map_err(BallotError::ConstraintProofFailed)?;
+
+    Ok(WeakOrderingProof {
+        score_commitments,
+        r1cs_proof
+    })
+}
+
+/// Sorted ascending, a tied Borda assignment starts at `0` and every entry
+/// either repeats the previous one (a tie) or equals its own index (a new
+/// group, whose members beat exactly the `j` candidates before it).
+fn is_weak_ordering(sorted_scores: &[u32]) -> bool {
+    sorted_scores.first() == Some(&0)
+        && sorted_scores
+            .windows(2)
+            .enumerate()
+            .all(|(j, pair)| pair[1] == pair[0] || pair[1] as usize == j + 1)
+}
+
+fn sorted(scores: &[u32]) -> Vec<u32> {
+    let mut sorted_scores = scores.to_vec();
+    sorted_scores.sort_unstable();
+    sorted_scores
+}
+
+/// Allocates the sorted scores `w`, constrains them as in [`is_weak_ordering`]
+/// and shuffles them into the committed scores. `sorted_scores` is the
+/// prover's witness, `None` for the verifier.
+fn weak_ordering_constraints<CS: RandomizableConstraintSystem>(
+    cs: &mut CS,
+    scores: Vec<Variable>,
+    sorted_scores: Option<Vec<u32>>,
+) -> Result<(), R1CSError> {
+    let sorted_variables: Vec<Variable> = (0..scores.len())
+        .map(|j| cs.allocate(sorted_scores.as_ref().map(|w| Scalar::from(w[j]))))
+        .collect::<Result<_, _>>()?;
+
+    cs.constrain(sorted_variables[0].into());
+    for j in 1..sorted_variables.len() {
+        let (_, _, o) = cs.multiply(
+            sorted_variables[j] - sorted_variables[j - 1],
+            sorted_variables[j] - Scalar::from(j as u64),
+        );
+        cs.constrain(o.into());
+    }
+
+    shuffle_constraints(cs, scores, sorted_variables)
+}
+
+/// Constrains `y` to be a permutation of `x` by checking
+/// `prod (x_i - z) = prod (y_i - z)` for a random challenge `z`.
+fn shuffle_constraints<CS: RandomizableConstraintSystem>(
+    cs: &mut CS,
+    x: Vec<Variable>,
+    y: Vec<Variable>,
+) -> Result<(), R1CSError> {
+    if x.len() == 1 {
+        cs.constrain(y[0] - x[0]);
+        return Ok(());
+    }
+
+    cs.specify_randomized_constraints(move |cs| {
+        let z = cs.challenge_scalar(b"shuffle challenge");
+        let product = |cs: &mut CS::RandomizedCS, vars: &[Variable]| {
+            let (_, _, first) = cs.multiply(vars[0] - z, vars[1] - z);
+            vars[2..].iter().fold(first, |acc, var| cs.multiply(acc.into(), *var - z).2)
+        };
+
+        let x_product = product(cs, &x);
+        let y_product = product(cs, &y);
+        cs.constrain(x_product - y_product);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ballot_validation::election_context::test_context;
+
+    fn cast(ballot_size: usize, scores: &[u32]) -> Result<(), VerificationError> {
+        let setup_params = setup(ballot_size, None).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), scores).expect("Should generate proof");
+        verify_proof(&setup_params, &test_context(), &proof)
+    }
+
+    #[test]
+    fn test_tied_and_strict_orderings_verify() {
+        assert!(cast(4, &[3, 1, 1, 0]).is_ok(), "A > B = C > D should verify");
+        assert!(cast(4, &[1, 3, 0, 2]).is_ok(), "Strict ordering should verify");
+        assert!(cast(4, &[0, 0, 0, 0]).is_ok(), "All candidates tied should verify");
+        assert!(cast(5, &[2, 2, 0, 2, 0]).is_ok(), "Two tie groups should verify");
+        assert!(cast(1, &[0]).is_ok(), "Single candidate should verify");
+    }
+
+    #[test]
+    fn test_scores_from_ranks() {
+        assert_eq!(scores_from_ranks(&[0, 1, 1, 2]), vec![3, 1, 1, 0]);
+        assert_eq!(scores_from_ranks(&[2, 0, 3, 1]), vec![1, 3, 0, 2]);
+        assert_eq!(scores_from_ranks(&[5, 5, 5]), vec![0, 0, 0]);
+        assert_eq!(scores_from_ranks(&[0, 0, 7]), vec![1, 1, 0]);
+    }
+
+    #[test]
+    fn test_invalid_assignments_are_rejected() {
+        let setup_params = setup(4, None).unwrap();
+
+        for scores in [[1, 1, 0, 0], [3, 3, 1, 0], [0, 1, 2, 4], [1, 1, 1, 1]] {
+            let result = generate_vote(&setup_params, &test_context(), &scores);
+            assert!(matches!(result, Err(BallotError::InvalidPermutation(_))), "{:?} should be rejected", scores);
+        }
+
+        assert_eq!(generate_vote(&setup_params, &test_context(), &[1, 0]).err(), Some(BallotError::LengthMismatch { expected: 4, actual: 2 }));
+    }
+
+    #[test]
+    fn test_forged_assignment_fails_verification() {
+        let setup_params = setup(4, None).unwrap();
+
+        // Averaged scores for A > B = C > D are not the tie rule this scheme uses.
+        let forged = prove_scores(&setup_params, &test_context(), &[3, 2, 2, 0], &mut OsRng).unwrap();
+        assert!(matches!(verify_proof(&setup_params, &test_context(), &forged), Err(VerificationError::ConstraintProofFailed(_))));
+
+        let mut swapped = prove_scores(&setup_params, &test_context(), &[3, 1, 1, 0], &mut OsRng).unwrap();
+        swapped.score_commitments[0] = forged.score_commitments[0];
+        assert!(matches!(verify_proof(&setup_params, &test_context(), &swapped), Err(VerificationError::ConstraintProofFailed(_))));
+    }
+
+    #[test]
+    fn test_proof_is_bound_to_context() {
+        let setup_params = setup(4, None).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), &[3, 1, 1, 0]).unwrap();
+
+        for context in [
+            ElectionContext::new(b"test election", b"other contest", b"test voter"),
+            ElectionContext::new(b"test election", b"test contest", b"other voter"),
+        ] {
+            assert!(matches!(verify_proof(&setup_params, &context, &proof), Err(VerificationError::ConstraintProofFailed(_))));
+        }
+    }
+
+    #[test]
+    fn test_batch_verification() {
+        let setup_params = setup(4, None).unwrap();
+        let contexts: Vec<ElectionContext> = (0..2)
+            .map(|voter| ElectionContext::new(b"test election", b"test contest", &[voter]))
+            .collect();
+        let proofs: Vec<WeakOrderingProof> = contexts
+            .iter()
+            .map(|context| generate_vote(&setup_params, context, &[3, 1, 1, 0]).unwrap())
+            .collect();
+
+        let batch: Vec<(&ElectionContext, &WeakOrderingProof)> = contexts.iter().zip(&proofs).collect();
+        assert!(verify_batch(&setup_params, &batch).is_ok());
+        assert!(verify_batch(&setup_params, &[]).is_ok());
+
+        // The second and fourth ballots are replayed under the other voter's context.
+        let batch = [(&contexts[0], &proofs[0]), (&contexts[0], &proofs[1]), (&contexts[1], &proofs[1]), (&contexts[1], &proofs[0])];
+        let invalid = verify_batch(&setup_params, &batch).unwrap_err().invalid;
+        assert_eq!(invalid.iter().map(|(index, _)| *index).collect::<Vec<usize>>(), vec![1, 3]);
+        assert!(invalid.iter().all(|(_, error)| matches!(error, VerificationError::ConstraintProofFailed(_))));
+    }
+}
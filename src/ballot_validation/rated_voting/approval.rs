@@ -0,0 +1,410 @@
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+use curve25519_dalek_ng::{ristretto::{CompressedRistretto, RistrettoPoint}, scalar::Scalar};
+use bulletproofs::{BulletproofGens, PedersenGens};
+use crate::ballot_validation::ballot_scheme::{verify_batched, BallotScheme};
+use crate::ballot_validation::election_context::ElectionContext;
+use crate::ballot_validation::encoding::{impl_serde_via_bytes, ObjectKind, Reader, SchemeTag, Writer};
+use crate::ballot_validation::errors::{BallotError, BatchVerificationError, DecodingError, VerificationError};
+use crate::ballot_validation::sigma::BatchCheck;
+use super::common::{prove_bits, prove_sum_bounds, range_proof_bit_size, verify_bits, verify_bits_deferred, verify_sum_bounds, AggregatedRangeProof, BitProof};
+
+/// Setup for approval voting: every vote is 0 or 1, and the number of
+/// approvals can optionally be bounded from below and above.
+pub struct SetupParameters {
+    pub(super) pc_gens: PedersenGens,
+    bp_gens: BulletproofGens,
+    bit_size: usize,
+    pub(super) ballot_size: usize,
+    pub(super) min_approvals: Option<u64>,
+    pub(super) max_approvals: Option<u64>,
+    encoding: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct ApprovalVotingProof {
+    vote_commitments: Vec<CompressedRistretto>,
+    bit_proofs: Vec<BitProof>,
+    pub(super) approvals_proof: Option<AggregatedRangeProof>,
+}
+
+impl SetupParameters {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encoding.clone()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::ApprovalVoting, ObjectKind::SetupParameters);
+        writer
+            .pc_gens(&self.pc_gens)
+            .u64(self.ballot_size as u64)
+            .optional_u64(self.min_approvals)
+            .optional_u64(self.max_approvals);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SetupParameters, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::ApprovalVoting, ObjectKind::SetupParameters)?;
+        let pc_gens = reader.pc_gens()?;
+        let ballot_size = reader.usize()?;
+        let min_approvals = reader.optional_u64()?;
+        let max_approvals = reader.optional_u64()?;
+        reader.finish()?;
+
+        setup(ballot_size, min_approvals, max_approvals, Some(pc_gens)).map_err(DecodingError::InvalidParameters)
+    }
+}
+
+impl ApprovalVotingProof {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::ApprovalVoting, ObjectKind::Proof);
+        self.write(&mut writer);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<ApprovalVotingProof, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::ApprovalVoting, ObjectKind::Proof)?;
+        let proof = ApprovalVotingProof::read(&mut reader)?;
+        reader.finish()?;
+
+        Ok(proof)
+    }
+
+    pub(super) fn write(&self, writer: &mut Writer) {
+        writer.points(&self.vote_commitments);
+        self.bit_proofs.iter().for_each(|proof| proof.write(writer));
+        writer.flag(self.approvals_proof.is_some());
+        if let Some((proof, commitments)) = &self.approvals_proof {
+            writer.range_proof(proof).points(commitments);
+        }
+    }
+
+    pub(super) fn read(reader: &mut Reader) -> Result<ApprovalVotingProof, DecodingError> {
+        let vote_commitments = reader.points()?;
+        let bit_proofs = vote_commitments
+            .iter()
+            .map(|_| BitProof::read(reader))
+            .collect::<Result<Vec<BitProof>, DecodingError>>()?;
+        let approvals_proof = match reader.flag()? {
+            true => Some((reader.range_proof()?, reader.points()?)),
+            false => None,
+        };
+
+        Ok(ApprovalVotingProof {
+            vote_commitments,
+            bit_proofs,
+            approvals_proof
+        })
+    }
+}
+
+impl_serde_via_bytes!(SetupParameters, "approval voting setup");
+impl_serde_via_bytes!(ApprovalVotingProof, "approval voting proof");
+
+pub struct ApprovalVoting;
+
+impl BallotScheme for ApprovalVoting {
+    type Params = SetupParameters;
+    type Ballot = [bool];
+    type Proof = ApprovalVotingProof;
+    type Error = BallotError;
+
+    fn generate_vote_with_rng<R: RngCore + CryptoRng>(params: &SetupParameters, context: &ElectionContext, ballot: &[bool], rng: &mut R) -> Result<ApprovalVotingProof, BallotError> {
+        generate_vote_with_rng(params, context, ballot, rng)
+    }
+
+    fn verify_proof(params: &SetupParameters, context: &ElectionContext, proof: &ApprovalVotingProof) -> Result<(), VerificationError> {
+        verify_proof(params, context, proof)
+    }
+
+    fn verify_batch(params: &SetupParameters, ballots: &[(&ElectionContext, &ApprovalVotingProof)]) -> Result<(), BatchVerificationError> {
+        verify_batch(params, ballots)
+    }
+}
+
+pub fn setup(
+    ballot_size: usize,
+    min_approvals: Option<u64>,
+    max_approvals: Option<u64>,
+    pc_gens: Option<PedersenGens>
+) -> Result<SetupParameters, BallotError> {
+    if ballot_size == 0 {
+        return Err(BallotError::InvalidSetup("ballot_size must be greater than zero".into()));
+    }
+
+    if max_approvals.is_some_and(|max| max > ballot_size as u64) {
+        return Err(BallotError::InvalidSetup("max_approvals must not exceed ballot_size".into()));
+    }
+
+    if min_approvals.is_some_and(|min| min > max_approvals.unwrap_or(ballot_size as u64)) {
+        return Err(BallotError::InvalidSetup("min_approvals must not exceed max_approvals or ballot_size".into()));
+    }
+
+    let bit_size: usize = range_proof_bit_size(ballot_size as u128)?;
+    let pc_gens: PedersenGens = pc_gens.unwrap_or_default();
+    let bp_gens: BulletproofGens = BulletproofGens::new(bit_size, 2);
+
+    let mut setup_params = SetupParameters {
+        pc_gens,
+        bp_gens,
+        bit_size,
+        ballot_size,
+        min_approvals,
+        max_approvals,
+        encoding: Vec::new(),
+    };
+    setup_params.encoding = setup_params.encode();
+
+    Ok(setup_params)
+}
+
+pub fn generate_vote(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[bool],
+) -> Result<ApprovalVotingProof, BallotError> {
+    generate_vote_with_rng(setup_params, context, ballot, &mut OsRng)
+}
+
+pub fn generate_vote_with_rng<R: RngCore + CryptoRng>(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[bool],
+    rng: &mut R,
+) -> Result<ApprovalVotingProof, BallotError> {
+    if ballot.len() != setup_params.ballot_size {
+        return Err(BallotError::LengthMismatch { expected: setup_params.ballot_size, actual: ballot.len() });
+    }
+
+    let (vote_commitments, ballot_blindings, bit_proofs) =
+        prove_bits(&mut context.scheme_transcript(b"approval voting", &setup_params.encoding), &setup_params.pc_gens, ballot, rng);
+
+    let approvals_proof = if setup_params.min_approvals.is_some() || setup_params.max_approvals.is_some() {
+        let z: u64 = ballot.iter().filter(|&&vote| vote).count() as u64;
+        let z_blinding: Scalar = ballot_blindings.iter().sum();
+
+        Some(prove_sum_bounds(
+            &setup_params.bp_gens,
+            &setup_params.pc_gens,
+            &mut context.scheme_transcript(b"approval voting bounds", &setup_params.encoding),
+            z,
+            z_blinding,
+            setup_params.min_approvals,
+            setup_params.max_approvals,
+            setup_params.bit_size,
+            rng,
+        )?)
+    } else {
+        None
+    };
+
+    Ok(ApprovalVotingProof {
+        vote_commitments: vote_commitments.iter().map(|commitment| commitment.compress()).collect(),
+        bit_proofs,
+        approvals_proof
+    })
+}
+
+pub fn verify_proof(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    validity_proof: &ApprovalVotingProof,
+) -> Result<(), VerificationError> {
+    let vote_commitments: Vec<RistrettoPoint> = verify_bits(
+        &mut context.scheme_transcript(b"approval voting", &setup_params.encoding),
+        &setup_params.pc_gens,
+        setup_params.ballot_size,
+        &validity_proof.vote_commitments,
+        &validity_proof.bit_proofs,
+    )?;
+
+    verify_approvals(setup_params, context, validity_proof, &vote_commitments)
+}
+
+/// The bit proofs of the whole batch share one multiscalar multiplication;
+/// the approval bounds are checked per ballot.
+pub fn verify_batch(
+    setup_params: &SetupParameters,
+    ballots: &[(&ElectionContext, &ApprovalVotingProof)],
+) -> Result<(), BatchVerificationError> {
+    verify_batched(
+        ballots,
+        BatchCheck::default(),
+        |batch, context, proof| {
+            let vote_commitments: Vec<RistrettoPoint> = verify_bits_deferred(
+                &mut context.scheme_transcript(b"approval voting", &setup_params.encoding),
+                &setup_params.pc_gens,
+                setup_params.ballot_size,
+                &proof.vote_commitments,
+                &proof.bit_proofs,
+                batch,
+                &mut OsRng,
+            )?;
+
+            verify_approvals(setup_params, context, proof, &vote_commitments)
+        },
+        |batch| batch.verify(),
+        |context, proof| verify_proof(setup_params, context, proof),
+    )
+}
+
+fn verify_approvals(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    validity_proof: &ApprovalVotingProof,
+    vote_commitments: &[RistrettoPoint],
+) -> Result<(), VerificationError> {
+    match &validity_proof.approvals_proof {
+        Some(approvals_proof) => verify_sum_bounds(
+            &setup_params.bp_gens,
+            &setup_params.pc_gens,
+            &mut context.scheme_transcript(b"approval voting bounds", &setup_params.encoding),
+            &vote_commitments.iter().sum(),
+            setup_params.min_approvals,
+            setup_params.max_approvals,
+            approvals_proof,
+            setup_params.bit_size,
+        ),
+        None if setup_params.min_approvals.is_some() || setup_params.max_approvals.is_some() => {
+            let expected = setup_params.min_approvals.iter().count() + setup_params.max_approvals.iter().count();
+            Err(VerificationError::LengthMismatch { expected, actual: 0 })
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ballot_validation::election_context::test_context;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_valid_ballots() {
+        let setup_params = setup(4, None, None, None).unwrap();
+
+        for ballot in [[false; 4], [true; 4], [true, false, false, true]] {
+            let proof = generate_vote(&setup_params, &test_context(), &ballot).expect("Should generate proof");
+            assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok(), "Any approval ballot should verify without limits");
+        }
+    }
+
+    #[test]
+    fn test_non_binary_vote_fails() {
+        let setup_params = setup(2, None, None, None).unwrap();
+        let mut proof = generate_vote(&setup_params, &test_context(), &[true, false]).unwrap();
+
+        let blinding = Scalar::random(&mut thread_rng());
+        let forged = setup_params.pc_gens.commit(Scalar::from(2u64), blinding);
+        let mut transcript = test_context().scheme_transcript(b"approval voting", &setup_params.encoding);
+        BitProof::prove(&mut transcript, &setup_params.pc_gens, &proof.vote_commitments[0].decompress().unwrap(), true, &blinding, &mut thread_rng());
+        proof.bit_proofs[1] = BitProof::prove(&mut transcript, &setup_params.pc_gens, &forged, true, &blinding, &mut thread_rng());
+        proof.vote_commitments[1] = forged.compress();
+
+        assert_eq!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::BitProofFailed { index: 1 }));
+    }
+
+    #[test]
+    fn test_approval_limits() {
+        let setup_params = setup(4, Some(1), Some(2), None).unwrap();
+
+        for ballot in [[true, false, false, false], [false, true, true, false]] {
+            let proof = generate_vote(&setup_params, &test_context(), &ballot).unwrap();
+            assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok());
+        }
+
+        assert_eq!(generate_vote(&setup_params, &test_context(), &[false; 4]).err(), Some(BallotError::BudgetBelowMinimum { sum: 0, min: 1 }));
+        assert_eq!(generate_vote(&setup_params, &test_context(), &[true, true, true, false]).err(), Some(BallotError::BudgetExceeded { sum: 3, max: 2 }));
+    }
+
+    #[test]
+    fn test_unlimited_proof_fails_limited_verification() {
+        let unlimited = setup(4, None, None, None).unwrap();
+        let limited = setup(4, None, Some(2), None).unwrap();
+
+        let mut proof = generate_vote(&limited, &test_context(), &[true, true, false, false]).unwrap();
+        let approvals_proof = proof.approvals_proof.take();
+        assert_eq!(verify_proof(&limited, &test_context(), &proof), Err(VerificationError::LengthMismatch { expected: 1, actual: 0 }));
+
+        let mut proof = generate_vote(&unlimited, &test_context(), &[true, true, false, false]).unwrap();
+        proof.approvals_proof = approvals_proof;
+        assert_eq!(verify_proof(&unlimited, &test_context(), &proof), Err(VerificationError::LengthMismatch { expected: 0, actual: 1 }));
+    }
+
+    #[test]
+    fn test_too_many_approvals_fail_the_range_proof() {
+        let setup_params = setup(4, None, Some(2), None).unwrap();
+        let (vote_commitments, blindings, bit_proofs) = prove_bits(
+            &mut test_context().scheme_transcript(b"approval voting", &setup_params.encoding),
+            &setup_params.pc_gens,
+            &[true, true, true, false],
+            &mut thread_rng(),
+        );
+
+        // 2 - 3 has no range proof, so prove 0 instead and claim the commitment the verifier derives.
+        let com_z: RistrettoPoint = vote_commitments.iter().sum();
+        let (range_proof, _) = prove_sum_bounds(
+            &setup_params.bp_gens,
+            &setup_params.pc_gens,
+            &mut test_context().scheme_transcript(b"approval voting bounds", &setup_params.encoding),
+            2,
+            blindings.iter().sum(),
+            None,
+            Some(2),
+            setup_params.bit_size,
+            &mut thread_rng(),
+        ).unwrap();
+        let bound_commitment = (setup_params.pc_gens.commit(Scalar::from(2u64), Scalar::zero()) - com_z).compress();
+
+        let proof = ApprovalVotingProof {
+            vote_commitments: vote_commitments.iter().map(|commitment| commitment.compress()).collect(),
+            bit_proofs,
+            approvals_proof: Some((range_proof, vec![bound_commitment])),
+        };
+        assert!(matches!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::RangeProofFailed(_))));
+    }
+
+    #[test]
+    fn test_invalid_setup() {
+        assert!(matches!(setup(0, None, None, None), Err(BallotError::InvalidSetup(_))));
+        assert!(matches!(setup(4, None, Some(5), None), Err(BallotError::InvalidSetup(_))));
+        assert!(matches!(setup(4, Some(3), Some(2), None), Err(BallotError::InvalidSetup(_))));
+        assert!(matches!(setup(4, Some(5), None, None), Err(BallotError::InvalidSetup(_))));
+        assert!(setup(3, Some(2), Some(2), None).is_ok());
+    }
+
+    #[test]
+    fn test_proof_is_bound_to_context_and_setup() {
+        let setup_params = setup(4, None, None, None).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), &[true, false, true, false]).unwrap();
+
+        for context in [
+            ElectionContext::new(b"test election", b"other contest", b"test voter"),
+            ElectionContext::new(b"test election", b"test contest", b"other voter"),
+        ] {
+            assert_eq!(verify_proof(&setup_params, &context, &proof), Err(VerificationError::BitProofFailed { index: 0 }));
+        }
+
+        let other = setup(4, None, Some(4), None).unwrap();
+        assert_eq!(verify_proof(&other, &test_context(), &proof), Err(VerificationError::BitProofFailed { index: 0 }));
+    }
+
+    #[test]
+    fn test_batch_verification() {
+        let setup_params = setup(4, None, None, None).unwrap();
+        let contexts: Vec<ElectionContext> = (0..2)
+            .map(|voter| ElectionContext::new(b"test election", b"test contest", &[voter]))
+            .collect();
+        let proofs: Vec<ApprovalVotingProof> = contexts
+            .iter()
+            .map(|context| generate_vote(&setup_params, context, &[true, false, true, false]).unwrap())
+            .collect();
+
+        let batch: Vec<(&ElectionContext, &ApprovalVotingProof)> = contexts.iter().zip(&proofs).collect();
+        assert!(verify_batch(&setup_params, &batch).is_ok());
+        assert!(verify_batch(&setup_params, &[]).is_ok());
+
+        // The second and fourth ballots are replayed under the other voter's context.
+        let batch = [(&contexts[0], &proofs[0]), (&contexts[0], &proofs[1]), (&contexts[1], &proofs[1]), (&contexts[1], &proofs[0])];
+        assert_eq!(verify_batch(&setup_params, &batch).unwrap_err().invalid, vec![(1, VerificationError::BitProofFailed { index: 0 }), (3, VerificationError::BitProofFailed { index: 0 })]);
+    }
+}
@@ -0,0 +1,353 @@
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+use curve25519_dalek_ng::{ristretto::{CompressedRistretto, RistrettoPoint}, scalar::Scalar};
+use bulletproofs::{BulletproofGens, PedersenGens, RangeProof};
+#[cfg(feature = "r1cs")]
+use bulletproofs::r1cs::{ConstraintSystem, LinearCombination, R1CSError};
+use crate::ballot_validation::encoding::{Reader, Writer};
+use crate::ballot_validation::errors::{BallotError, DecodingError, VerificationError};
+use crate::ballot_validation::sigma::BatchCheck;
+
+const SUPPORTED_BIT_SIZES: [usize; 4] = [8, 16, 32, 64];
+
+/// Smallest Bulletproofs bit size whose range `[0, 2^n)` contains `upper_bound`.
+pub(crate) fn range_proof_bit_size(upper_bound: u128) -> Result<usize, BallotError> {
+    SUPPORTED_BIT_SIZES
+        .into_iter()
+        .find(|&n| upper_bound < 1u128 << n)
+        .ok_or_else(|| BallotError::InvalidSetup(format!("bound {} does not fit in a 64-bit range proof", upper_bound)))
+}
+
+/// Range proof aggregated over several committed values, with their commitments.
+pub(crate) type AggregatedRangeProof = (RangeProof, Vec<CompressedRistretto>);
+
+/// Proves `min <= z <= max` for a committed sum `z` by range-proving `max - z`
+/// and `z - min` (for whichever bounds are set) in one aggregated proof.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn prove_sum_bounds<R: RngCore + CryptoRng>(
+    bp_gens: &BulletproofGens,
+    pc_gens: &PedersenGens,
+    transcript: &mut Transcript,
+    z: u64,
+    z_blinding: Scalar,
+    min: Option<u64>,
+    max: Option<u64>,
+    bit_size: usize,
+    rng: &mut R,
+) -> Result<AggregatedRangeProof, BallotError> {
+    let mut values: Vec<u64> = Vec::with_capacity(2);
+    let mut blindings: Vec<Scalar> = Vec::with_capacity(2);
+
+    if let Some(max) = max {
+        values.push(max.checked_sub(z).ok_or(BallotError::BudgetExceeded { sum: z, max })?);
+        blindings.push(-z_blinding);
+    }
+    if let Some(min) = min {
+        values.push(z.checked_sub(min).ok_or(BallotError::BudgetBelowMinimum { sum: z, min })?);
+        blindings.push(z_blinding);
+    }
+
+    RangeProof::prove_multiple_with_rng(bp_gens, pc_gens, transcript, &values, &blindings, bit_size, rng)
+        .map_err(BallotError::RangeProofFailed)
+}
+
+/// Checks a proof from [`prove_sum_bounds`] against the verifier's own
+/// commitment to the sum.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn verify_sum_bounds(
+    bp_gens: &BulletproofGens,
+    pc_gens: &PedersenGens,
+    transcript: &mut Transcript,
+    com_z: &RistrettoPoint,
+    min: Option<u64>,
+    max: Option<u64>,
+    proof: &AggregatedRangeProof,
+    bit_size: usize,
+) -> Result<(), VerificationError> {
+    let mut bound_commitments: Vec<CompressedRistretto> = Vec::with_capacity(2);
+    if let Some(max) = max {
+        bound_commitments.push((pc_gens.commit(Scalar::from(max), Scalar::zero()) - com_z).compress());
+    }
+    if let Some(min) = min {
+        bound_commitments.push((com_z - pc_gens.commit(Scalar::from(min), Scalar::zero())).compress());
+    }
+
+    if proof.1.len() != bound_commitments.len() {
+        return Err(VerificationError::LengthMismatch { expected: bound_commitments.len(), actual: proof.1.len() });
+    }
+    if proof.1 != bound_commitments {
+        return Err(VerificationError::CommitmentMismatch);
+    }
+
+    proof.0
+        .verify_multiple(bp_gens, pc_gens, transcript, &bound_commitments, bit_size)
+        .map_err(VerificationError::RangeProofFailed)
+}
+
+/// Number of bits needed to write `value` in binary (at least one).
+#[cfg(feature = "r1cs")]
+pub(crate) fn bit_length(value: u64) -> usize {
+    (u64::BITS - value.leading_zeros()).max(1) as usize
+}
+
+/// Constrains `value` to `[0, 2^n_bits)` by decomposing it into bits, one
+/// multiplier per bit. `assignment` is the prover's witness, `None` for the verifier.
+#[cfg(feature = "r1cs")]
+pub(crate) fn range_constraint<CS: ConstraintSystem>(
+    cs: &mut CS,
+    value: LinearCombination,
+    assignment: Option<u64>,
+    n_bits: usize,
+) -> Result<(), R1CSError> {
+    let mut recomposed = LinearCombination::default();
+    let mut exp_2 = Scalar::one();
+
+    for i in 0..n_bits {
+        let (a, b, o) = cs.allocate_multiplier(assignment.map(|q| {
+            let bit: u64 = (q >> i) & 1;
+            (Scalar::from(1 - bit), Scalar::from(bit))
+        }))?;
+
+        // a * b = 0 and a = 1 - b force b to be a bit.
+        cs.constrain(o.into());
+        cs.constrain(a + (b - Scalar::one()));
+
+        recomposed = recomposed + b * exp_2;
+        exp_2 = exp_2 + exp_2;
+    }
+
+    cs.constrain(value - recomposed);
+    Ok(())
+}
+
+/// Disjunctive (CDS) proof that a Pedersen commitment opens to 0 or to 1,
+/// without revealing which. The nonce commitments are part of the proof, so
+/// its checks can be deferred into a [`BatchCheck`].
+#[derive(Clone)]
+pub(crate) struct BitProof {
+    pub(crate) nonce_commitments: [CompressedRistretto; 2],
+    /// Challenge of the `0` branch; the `1` branch gets the rest of the
+    /// Fiat-Shamir challenge.
+    pub(crate) challenge: Scalar,
+    pub(crate) responses: [Scalar; 2],
+}
+
+impl BitProof {
+    pub(crate) fn prove<R: RngCore + CryptoRng>(
+        transcript: &mut Transcript,
+        pc_gens: &PedersenGens,
+        commitment: &RistrettoPoint,
+        bit: bool,
+        blinding: &Scalar,
+        rng: &mut R,
+    ) -> BitProof {
+        // statements[j] = commitment - j * B, which is a multiple of B_blinding for j = bit.
+        let statements = [*commitment, commitment - pc_gens.B];
+        let real = bit as usize;
+        let simulated = 1 - real;
+
+        let mut challenges = [Scalar::zero(); 2];
+        let mut responses = [Scalar::zero(); 2];
+        let mut nonce_commitments = [CompressedRistretto::default(); 2];
+
+        challenges[simulated] = Scalar::random(rng);
+        responses[simulated] = Scalar::random(rng);
+        nonce_commitments[simulated] = (responses[simulated] * pc_gens.B_blinding - challenges[simulated] * statements[simulated]).compress();
+
+        let nonce = Scalar::random(rng);
+        nonce_commitments[real] = (nonce * pc_gens.B_blinding).compress();
+
+        let challenge = bit_challenge(transcript, commitment, &nonce_commitments);
+        challenges[real] = challenge - challenges[simulated];
+        responses[real] = nonce + challenges[real] * blinding;
+
+        BitProof { nonce_commitments, challenge: challenges[0], responses }
+    }
+
+    pub(crate) fn verify(
+        &self,
+        transcript: &mut Transcript,
+        pc_gens: &PedersenGens,
+        commitment: &RistrettoPoint,
+    ) -> bool {
+        let statements = [*commitment, commitment - pc_gens.B];
+        match self.branches(transcript, commitment) {
+            Some((challenges, nonce_commitments)) => (0..2).all(|j| {
+                self.responses[j] * pc_gens.B_blinding == nonce_commitments[j] + challenges[j] * statements[j]
+            }),
+            None => false,
+        }
+    }
+
+    /// Like `verify`, but adds both branch equations to `batch` instead of
+    /// checking them. Returns `false` if the proof is malformed.
+    pub(crate) fn verify_deferred<R: RngCore + CryptoRng>(
+        &self,
+        transcript: &mut Transcript,
+        pc_gens: &PedersenGens,
+        commitment: &RistrettoPoint,
+        batch: &mut BatchCheck,
+        rng: &mut R,
+    ) -> bool {
+        let statements = [*commitment, commitment - pc_gens.B];
+        match self.branches(transcript, commitment) {
+            Some((challenges, nonce_commitments)) => {
+                for j in 0..2 {
+                    batch.add(rng, &[(self.responses[j], pc_gens.B_blinding), (-Scalar::one(), nonce_commitments[j]), (-challenges[j], statements[j])]);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Recomputes the Fiat-Shamir challenge and returns the challenge and
+    /// nonce commitment of each branch, or `None` for a malformed point.
+    fn branches(&self, transcript: &mut Transcript, commitment: &RistrettoPoint) -> Option<([Scalar; 2], [RistrettoPoint; 2])> {
+        let challenge = bit_challenge(transcript, commitment, &self.nonce_commitments);
+        let nonce_commitments = [self.nonce_commitments[0].decompress()?, self.nonce_commitments[1].decompress()?];
+        Some(([self.challenge, challenge - self.challenge], nonce_commitments))
+    }
+
+    pub(crate) fn write(&self, writer: &mut Writer) {
+        writer
+            .point(&self.nonce_commitments[0])
+            .point(&self.nonce_commitments[1])
+            .scalar(&self.challenge)
+            .scalar(&self.responses[0])
+            .scalar(&self.responses[1]);
+    }
+
+    pub(crate) fn read(reader: &mut Reader) -> Result<BitProof, DecodingError> {
+        Ok(BitProof {
+            nonce_commitments: [reader.point()?, reader.point()?],
+            challenge: reader.scalar()?,
+            responses: [reader.scalar()?, reader.scalar()?],
+        })
+    }
+}
+
+/// Commits to every entry of a 0/1 ballot and proves each commitment is a
+/// bit. Returns the commitments, their blindings and the bit proofs.
+pub(crate) fn prove_bits<R: RngCore + CryptoRng>(
+    transcript: &mut Transcript,
+    pc_gens: &PedersenGens,
+    ballot: &[bool],
+    rng: &mut R,
+) -> (Vec<RistrettoPoint>, Vec<Scalar>, Vec<BitProof>) {
+    let blindings: Vec<Scalar> = (0..ballot.len())
+        .map(|_| Scalar::random(rng))
+        .collect();
+    let commitments: Vec<RistrettoPoint> = ballot
+        .iter()
+        .zip(&blindings)
+        .map(|(&vote, blinding)| pc_gens.commit(Scalar::from(vote as u64), *blinding))
+        .collect();
+    let proofs: Vec<BitProof> = ballot
+        .iter()
+        .zip(commitments.iter().zip(&blindings))
+        .map(|(&vote, (commitment, blinding))| BitProof::prove(transcript, pc_gens, commitment, vote, blinding, rng))
+        .collect();
+
+    (commitments, blindings, proofs)
+}
+
+/// Checks the proofs from [`prove_bits`] and returns the decompressed commitments.
+pub(crate) fn verify_bits(
+    transcript: &mut Transcript,
+    pc_gens: &PedersenGens,
+    ballot_size: usize,
+    commitments: &[CompressedRistretto],
+    proofs: &[BitProof],
+) -> Result<Vec<RistrettoPoint>, VerificationError> {
+    check_bits(ballot_size, commitments, proofs, |proof, commitment| proof.verify(transcript, pc_gens, commitment))
+}
+
+/// Like [`verify_bits`], but defers the bit proofs into `batch`.
+pub(crate) fn verify_bits_deferred<R: RngCore + CryptoRng>(
+    transcript: &mut Transcript,
+    pc_gens: &PedersenGens,
+    ballot_size: usize,
+    commitments: &[CompressedRistretto],
+    proofs: &[BitProof],
+    batch: &mut BatchCheck,
+    rng: &mut R,
+) -> Result<Vec<RistrettoPoint>, VerificationError> {
+    check_bits(ballot_size, commitments, proofs, |proof, commitment| proof.verify_deferred(transcript, pc_gens, commitment, batch, rng))
+}
+
+fn check_bits(
+    ballot_size: usize,
+    commitments: &[CompressedRistretto],
+    proofs: &[BitProof],
+    mut check: impl FnMut(&BitProof, &RistrettoPoint) -> bool,
+) -> Result<Vec<RistrettoPoint>, VerificationError> {
+    if commitments.len() != ballot_size {
+        return Err(VerificationError::LengthMismatch { expected: ballot_size, actual: commitments.len() });
+    }
+    if proofs.len() != ballot_size {
+        return Err(VerificationError::LengthMismatch { expected: ballot_size, actual: proofs.len() });
+    }
+
+    let commitments: Vec<RistrettoPoint> = commitments
+        .iter()
+        .map(|commitment| commitment.decompress().ok_or(VerificationError::DecompressionFailed))
+        .collect::<Result<Vec<RistrettoPoint>, VerificationError>>()?;
+
+    for (index, (commitment, proof)) in commitments.iter().zip(proofs).enumerate() {
+        if !check(proof, commitment) {
+            return Err(VerificationError::BitProofFailed { index });
+        }
+    }
+
+    Ok(commitments)
+}
+
+fn bit_challenge(
+    transcript: &mut Transcript,
+    commitment: &RistrettoPoint,
+    nonce_commitments: &[CompressedRistretto; 2],
+) -> Scalar {
+    transcript.append_message(b"bit commitment", commitment.compress().as_bytes());
+    transcript.append_message(b"bit nonce 0", nonce_commitments[0].as_bytes());
+    transcript.append_message(b"bit nonce 1", nonce_commitments[1].as_bytes());
+    let mut challenge = [0u8; 64];
+    transcript.challenge_bytes(b"bit challenge", &mut challenge);
+    Scalar::from_bytes_mod_order_wide(&challenge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_bit_size_is_smallest_fitting_width() {
+        assert_eq!(range_proof_bit_size(0), Ok(8));
+        assert_eq!(range_proof_bit_size(255), Ok(8));
+        assert_eq!(range_proof_bit_size(256), Ok(16));
+        assert_eq!(range_proof_bit_size(u32::MAX as u128 + 1), Ok(64));
+        assert_eq!(range_proof_bit_size(u64::MAX as u128), Ok(64));
+        assert!(range_proof_bit_size(u64::MAX as u128 + 1).is_err());
+    }
+
+    #[test]
+    fn test_bit_proof() {
+        let pc_gens = PedersenGens::default();
+        let blinding = Scalar::random(&mut thread_rng());
+
+        for bit in [false, true] {
+            let commitment = pc_gens.commit(Scalar::from(bit as u64), blinding);
+            let proof = BitProof::prove(&mut Transcript::new(b"test"), &pc_gens, &commitment, bit, &blinding, &mut thread_rng());
+            assert!(proof.verify(&mut Transcript::new(b"test"), &pc_gens, &commitment));
+        }
+
+        let two = pc_gens.commit(Scalar::from(2u64), blinding);
+        let forged = BitProof::prove(&mut Transcript::new(b"test"), &pc_gens, &two, true, &blinding, &mut thread_rng());
+        assert!(!forged.verify(&mut Transcript::new(b"test"), &pc_gens, &two));
+
+        let mut batch = BatchCheck::default();
+        assert!(forged.verify_deferred(&mut Transcript::new(b"test"), &pc_gens, &two, &mut batch, &mut thread_rng()));
+        assert!(!batch.verify(), "A bit proof for 2 should fail the batch");
+    }
+}
@@ -0,0 +1,328 @@
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+use curve25519_dalek_ng::{ristretto::{CompressedRistretto, RistrettoPoint}, scalar::Scalar};
+use bulletproofs::PedersenGens;
+use crate::ballot_validation::ballot_scheme::{verify_batched, BallotScheme};
+use crate::ballot_validation::election_context::ElectionContext;
+use crate::ballot_validation::encoding::{impl_serde_via_bytes, ObjectKind, Reader, SchemeTag, Writer};
+use crate::ballot_validation::errors::{BallotError, BatchVerificationError, DecodingError, VerificationError};
+use crate::ballot_validation::sigma::{BatchCheck, DiscreteLogProof};
+use crate::ballot_validation::tally::{CandidateOpening, TallyScheme};
+use super::max_budget::{self, openings, prove_votes, verify_votes, MaxBudgetRatedVoting, VotesProof};
+
+/// Setup for cumulative voting where every ballot must spend exactly the
+/// budget. The vote range proofs are shared with `max_budget`.
+pub struct SetupParameters {
+    inner: max_budget::SetupParameters,
+    encoding: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct ExactBudgetRatedVotingProof {
+    votes_proof: VotesProof,
+    opening_proof: DiscreteLogProof,
+}
+
+impl SetupParameters {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encoding.clone()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::ExactBudgetRatedVoting, ObjectKind::SetupParameters);
+        writer
+            .pc_gens(&self.inner.pc_gens)
+            .u64(self.inner.max_credit.value)
+            .optional_u64(self.inner.max_per_candidate.as_ref().map(|cap| cap.value))
+            .u64(self.inner.ballot_size as u64);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SetupParameters, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::ExactBudgetRatedVoting, ObjectKind::SetupParameters)?;
+        let pc_gens = reader.pc_gens()?;
+        let budget = reader.u64()?;
+        let max_per_candidate = reader.optional_u64()?;
+        let ballot_size = reader.usize()?;
+        reader.finish()?;
+
+        setup(budget, max_per_candidate, ballot_size, Some(pc_gens)).map_err(DecodingError::InvalidParameters)
+    }
+}
+
+impl ExactBudgetRatedVotingProof {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::ExactBudgetRatedVoting, ObjectKind::Proof);
+        writer
+            .range_proof(&self.votes_proof.0)
+            .points(&self.votes_proof.1);
+        self.opening_proof.write(&mut writer);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<ExactBudgetRatedVotingProof, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::ExactBudgetRatedVoting, ObjectKind::Proof)?;
+        let votes_proof = (reader.range_proof()?, reader.points()?);
+        let opening_proof = DiscreteLogProof::read(&mut reader)?;
+        reader.finish()?;
+
+        Ok(ExactBudgetRatedVotingProof {
+            votes_proof,
+            opening_proof,
+        })
+    }
+}
+
+impl_serde_via_bytes!(SetupParameters, "exact budget rated voting setup");
+impl_serde_via_bytes!(ExactBudgetRatedVotingProof, "exact budget rated voting proof");
+
+pub struct ExactBudgetRatedVoting;
+
+impl BallotScheme for ExactBudgetRatedVoting {
+    type Params = SetupParameters;
+    type Ballot = [u64];
+    type Proof = ExactBudgetRatedVotingProof;
+    type Error = BallotError;
+
+    fn generate_vote_with_rng<R: RngCore + CryptoRng>(params: &SetupParameters, context: &ElectionContext, ballot: &[u64], rng: &mut R) -> Result<ExactBudgetRatedVotingProof, BallotError> {
+        generate_vote_with_rng(params, context, ballot, rng)
+    }
+
+    fn verify_proof(params: &SetupParameters, context: &ElectionContext, proof: &ExactBudgetRatedVotingProof) -> Result<(), VerificationError> {
+        verify_proof(params, context, proof)
+    }
+
+    fn verify_batch(params: &SetupParameters, ballots: &[(&ElectionContext, &ExactBudgetRatedVotingProof)]) -> Result<(), BatchVerificationError> {
+        verify_batch(params, ballots)
+    }
+}
+
+impl TallyScheme for ExactBudgetRatedVoting {
+    fn tally_setup(params: &SetupParameters) -> (PedersenGens, usize, i64) {
+        (params.inner.pc_gens, params.inner.ballot_size, 0)
+    }
+
+    fn setup_bytes(params: &SetupParameters) -> &[u8] {
+        &params.encoding
+    }
+
+    fn vote_bounds(params: &SetupParameters) -> (i128, i128) {
+        MaxBudgetRatedVoting::vote_bounds(&params.inner)
+    }
+
+    fn vote_commitments(proof: &ExactBudgetRatedVotingProof) -> &[CompressedRistretto] {
+        &proof.votes_proof.1
+    }
+
+    fn generate_vote_with_openings<R: RngCore + CryptoRng>(params: &SetupParameters, context: &ElectionContext, ballot: &[u64], rng: &mut R) -> Result<(ExactBudgetRatedVotingProof, Vec<CandidateOpening>), BallotError> {
+        generate_vote_with_openings(params, context, ballot, rng)
+    }
+}
+
+pub fn setup(
+    budget: u64,
+    max_per_candidate: Option<u64>,
+    ballot_size: usize,
+    pc_gens: Option<PedersenGens>
+) -> Result<SetupParameters, BallotError> {
+    let mut setup_params = SetupParameters {
+        inner: max_budget::setup(budget, None, max_per_candidate, ballot_size, pc_gens)?,
+        encoding: Vec::new(),
+    };
+    setup_params.encoding = setup_params.encode();
+
+    Ok(setup_params)
+}
+
+pub fn generate_vote(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[u64],
+) -> Result<ExactBudgetRatedVotingProof, BallotError> {
+    generate_vote_with_rng(setup_params, context, ballot, &mut OsRng)
+}
+
+pub fn generate_vote_with_rng<R: RngCore + CryptoRng>(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[u64],
+    rng: &mut R,
+) -> Result<ExactBudgetRatedVotingProof, BallotError> {
+    generate_vote_with_openings(setup_params, context, ballot, rng).map(|(proof, _)| proof)
+}
+
+pub fn generate_vote_with_openings<R: RngCore + CryptoRng>(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[u64],
+    rng: &mut R,
+) -> Result<(ExactBudgetRatedVotingProof, Vec<CandidateOpening>), BallotError> {
+    let (votes_proof, ballot_blindings) = prove_votes(&setup_params.inner, context, ballot, rng)?;
+
+    let z: u64 = ballot
+    .iter()
+    .try_fold(0u64, |acc, &val| acc.checked_add(val))
+    .ok_or(BallotError::Overflow)?;
+    if z != setup_params.inner.max_credit.value {
+        return Err(BallotError::BudgetMismatch { sum: z, budget: setup_params.inner.max_credit.value });
+    }
+
+    // budget - com_z = -z_blinding * B_blinding once the values cancel out.
+    let z_blinding: Scalar = ballot_blindings.iter().sum();
+    let com_z: RistrettoPoint = setup_params.inner.pc_gens.commit(Scalar::from(z), z_blinding);
+    let remainder: RistrettoPoint = setup_params.inner.max_credit.commitment - com_z;
+
+    let opening_proof = DiscreteLogProof::prove(
+        &mut context.scheme_transcript(b"exact budget rated voting", &setup_params.encoding),
+        &setup_params.inner.pc_gens.B_blinding,
+        &remainder,
+        &-z_blinding,
+        rng,
+    );
+
+    Ok((ExactBudgetRatedVotingProof {
+        votes_proof,
+        opening_proof,
+    }, openings(ballot, &ballot_blindings)))
+}
+
+pub fn verify_proof(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    validity_proof: &ExactBudgetRatedVotingProof,
+) -> Result<(), VerificationError> {
+    let com_z: RistrettoPoint = verify_votes(&setup_params.inner, context, &validity_proof.votes_proof)?.iter().sum();
+    let remainder: RistrettoPoint = setup_params.inner.max_credit.commitment - com_z;
+
+    if !validity_proof.opening_proof.verify(
+        &mut context.scheme_transcript(b"exact budget rated voting", &setup_params.encoding),
+        &setup_params.inner.pc_gens.B_blinding,
+        &remainder,
+    ) {
+        return Err(VerificationError::OpeningProofFailed);
+    }
+
+    Ok(())
+}
+
+/// The budget openings of the whole batch share one multiscalar
+/// multiplication; the vote range proofs are checked per ballot.
+pub fn verify_batch(
+    setup_params: &SetupParameters,
+    ballots: &[(&ElectionContext, &ExactBudgetRatedVotingProof)],
+) -> Result<(), BatchVerificationError> {
+    verify_batched(
+        ballots,
+        BatchCheck::default(),
+        |batch, context, proof| {
+            let com_z: RistrettoPoint = verify_votes(&setup_params.inner, context, &proof.votes_proof)?.iter().sum();
+            let remainder: RistrettoPoint = setup_params.inner.max_credit.commitment - com_z;
+
+            if !proof.opening_proof.verify_deferred(
+                &mut context.scheme_transcript(b"exact budget rated voting", &setup_params.encoding),
+                &setup_params.inner.pc_gens.B_blinding,
+                &remainder,
+                batch,
+                &mut OsRng,
+            ) {
+                return Err(VerificationError::OpeningProofFailed);
+            }
+
+            Ok(())
+        },
+        |batch| batch.verify(),
+        |context, proof| verify_proof(setup_params, context, proof),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ballot_validation::election_context::test_context;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_exact_spend_verifies() {
+        let setup_params = setup(12, None, 4, None).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), &[5, 3, 4, 0]).expect("Should generate proof");
+        assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok(), "Ballot spending the whole budget should verify");
+    }
+
+    #[test]
+    fn test_underspending_fails() {
+        let setup_params = setup(12, None, 4, None).unwrap();
+        let result = generate_vote(&setup_params, &test_context(), &[5, 3, 3, 0]);
+        assert_eq!(result.err(), Some(BallotError::BudgetMismatch { sum: 11, budget: 12 }));
+
+        let result = generate_vote(&setup_params, &test_context(), &[5, 3, 5, 0]);
+        assert_eq!(result.err(), Some(BallotError::BudgetMismatch { sum: 13, budget: 12 }));
+    }
+
+    #[test]
+    fn test_proof_for_other_budget_fails() {
+        let setup_params = setup(12, None, 2, None).unwrap();
+
+        // The vote range proofs hold under this setup, but the votes only add
+        // up to 10, so the remainder is not a multiple of the blinding base.
+        let (votes_proof, blindings) = prove_votes(&setup_params.inner, &test_context(), &[5, 5], &mut thread_rng()).unwrap();
+        let z_blinding: Scalar = blindings.iter().sum();
+        let remainder: RistrettoPoint = setup_params.inner.max_credit.commitment - setup_params.inner.pc_gens.commit(Scalar::from(10u64), z_blinding);
+        let opening_proof = DiscreteLogProof::prove(
+            &mut test_context().scheme_transcript(b"exact budget rated voting", &setup_params.encoding),
+            &setup_params.inner.pc_gens.B_blinding,
+            &remainder,
+            &-z_blinding,
+            &mut thread_rng(),
+        );
+
+        let proof = ExactBudgetRatedVotingProof { votes_proof, opening_proof };
+        assert_eq!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::OpeningProofFailed));
+    }
+
+    #[test]
+    fn test_per_candidate_cap() {
+        let setup_params = setup(12, Some(6), 2, None).unwrap();
+        assert!(verify_proof(&setup_params, &test_context(), &generate_vote(&setup_params, &test_context(), &[6, 6]).unwrap()).is_ok());
+        assert_eq!(generate_vote(&setup_params, &test_context(), &[7, 5]).err(), Some(BallotError::VoteOutOfRange { index: 0 }));
+    }
+
+    #[test]
+    fn test_setup_of_other_scheme_is_rejected() {
+        let setup_params = setup(300, Some(200), 4, None).unwrap();
+        assert_eq!(max_budget::SetupParameters::from_bytes(&setup_params.to_bytes()).err(), Some(DecodingError::UnexpectedTag));
+    }
+
+    #[test]
+    fn test_batch_verification() {
+        let setup_params = setup(12, None, 4, None).unwrap();
+        let context = test_context();
+        let ballots = [[5, 3, 4, 0], [0, 0, 0, 12], [3, 3, 3, 3]];
+        let mut proofs: Vec<ExactBudgetRatedVotingProof> = ballots
+            .iter()
+            .map(|ballot| generate_vote(&setup_params, &context, ballot).unwrap())
+            .collect();
+
+        let batch: Vec<(&ElectionContext, &ExactBudgetRatedVotingProof)> = proofs.iter().map(|proof| (&context, proof)).collect();
+        assert!(verify_batch(&setup_params, &batch).is_ok());
+
+        // Range proofs still pass, so only the batched opening check catches this.
+        proofs[1].opening_proof = proofs[0].opening_proof.clone();
+        let batch: Vec<(&ElectionContext, &ExactBudgetRatedVotingProof)> = proofs.iter().map(|proof| (&context, proof)).collect();
+        assert_eq!(verify_batch(&setup_params, &batch).unwrap_err().invalid, vec![(1, VerificationError::OpeningProofFailed)]);
+    }
+
+    #[test]
+    fn test_proof_is_bound_to_context_and_setup() {
+        let setup_params = setup(10, None, 4, None).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), &[4, 0, 3, 3]).unwrap();
+
+        for context in [
+            ElectionContext::new(b"test election", b"other contest", b"test voter"),
+            ElectionContext::new(b"test election", b"test contest", b"other voter"),
+        ] {
+            assert!(matches!(verify_proof(&setup_params, &context, &proof), Err(VerificationError::RangeProofFailed(_))));
+        }
+
+        let other = setup(10, Some(4), 4, None).unwrap();
+        assert!(matches!(verify_proof(&other, &test_context(), &proof), Err(VerificationError::RangeProofFailed(_))));
+    }
+}
@@ -1,11 +1,16 @@
-use rand::thread_rng;
-use merlin::Transcript;
+use rand::{rngs::OsRng, CryptoRng, RngCore};
 use curve25519_dalek_ng::{ristretto::{CompressedRistretto, RistrettoPoint}, scalar::Scalar};
 use bulletproofs::{BulletproofGens, PedersenGens, RangeProof};
+use crate::ballot_validation::ballot_scheme::{verify_each, BallotScheme};
+use crate::ballot_validation::election_context::ElectionContext;
+use crate::ballot_validation::encoding::{impl_serde_via_bytes, ObjectKind, Reader, SchemeTag, Writer};
+use crate::ballot_validation::errors::{BallotError, BatchVerificationError, DecodingError, VerificationError};
+use crate::ballot_validation::tally::{CandidateOpening, TallyScheme};
+use super::common::{prove_sum_bounds, range_proof_bit_size, verify_sum_bounds, AggregatedRangeProof};
 
 pub struct MaxCredit {
-    commitment: RistrettoPoint,
-    value: u64,
+    pub(super) commitment: RistrettoPoint,
+    pub(super) value: u64,
 }
 
 impl MaxCredit {
@@ -20,142 +25,349 @@ impl MaxCredit {
 }
 
 pub struct SetupParameters {
-    pc_gens: PedersenGens,
+    pub(super) pc_gens: PedersenGens,
     bp_gens: BulletproofGens,
-    ballot_size: usize,
-    max_credit: MaxCredit,
+    bit_size: usize,
+    pub(super) ballot_size: usize,
+    pub(super) max_credit: MaxCredit,
+    min_credit: Option<MaxCredit>,
+    pub(super) max_per_candidate: Option<MaxCredit>,
+    encoding: Vec<u8>,
 }
 
+/// Aggregated range proof over the votes, with the vote commitments.
+pub(super) type VotesProof = AggregatedRangeProof;
+
 #[derive(Clone)] 
 pub struct MaxBudgetRatedVotingProof {
-    votes_proof: (RangeProof, Vec<CompressedRistretto>), 
+    votes_proof: VotesProof, 
     com_z: RistrettoPoint, 
-    rangeproof_d: (RangeProof, CompressedRistretto)
+    rangeproof_d: AggregatedRangeProof
+}
+
+impl SetupParameters {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encoding.clone()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::MaxBudgetRatedVoting, ObjectKind::SetupParameters);
+        writer
+            .pc_gens(&self.pc_gens)
+            .u64(self.max_credit.value)
+            .optional_u64(self.min_credit.as_ref().map(|min| min.value))
+            .optional_u64(self.max_per_candidate.as_ref().map(|cap| cap.value))
+            .u64(self.ballot_size as u64);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SetupParameters, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::MaxBudgetRatedVoting, ObjectKind::SetupParameters)?;
+        let pc_gens = reader.pc_gens()?;
+        let max_credits = reader.u64()?;
+        let min_credits = reader.optional_u64()?;
+        let max_per_candidate = reader.optional_u64()?;
+        let ballot_size = reader.usize()?;
+        reader.finish()?;
+
+        setup(max_credits, min_credits, max_per_candidate, ballot_size, Some(pc_gens)).map_err(DecodingError::InvalidParameters)
+    }
+}
+
+impl MaxBudgetRatedVotingProof {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::MaxBudgetRatedVoting, ObjectKind::Proof);
+        writer
+            .range_proof(&self.votes_proof.0)
+            .points(&self.votes_proof.1)
+            .point(&self.com_z.compress())
+            .range_proof(&self.rangeproof_d.0)
+            .points(&self.rangeproof_d.1);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<MaxBudgetRatedVotingProof, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::MaxBudgetRatedVoting, ObjectKind::Proof)?;
+        let votes_proof = (reader.range_proof()?, reader.points()?);
+        let com_z = reader.decompressed_point()?;
+        let rangeproof_d = (reader.range_proof()?, reader.points()?);
+        reader.finish()?;
+
+        Ok(MaxBudgetRatedVotingProof {
+            votes_proof,
+            com_z,
+            rangeproof_d
+        })
+    }
+}
+
+impl_serde_via_bytes!(SetupParameters, "max budget rated voting setup");
+impl_serde_via_bytes!(MaxBudgetRatedVotingProof, "max budget rated voting proof");
+
+pub struct MaxBudgetRatedVoting;
+
+impl BallotScheme for MaxBudgetRatedVoting {
+    type Params = SetupParameters;
+    type Ballot = [u64];
+    type Proof = MaxBudgetRatedVotingProof;
+    type Error = BallotError;
+
+    fn generate_vote_with_rng<R: RngCore + CryptoRng>(params: &SetupParameters, context: &ElectionContext, ballot: &[u64], rng: &mut R) -> Result<MaxBudgetRatedVotingProof, BallotError> {
+        generate_vote_with_rng(params, context, ballot, rng)
+    }
+
+    fn verify_proof(params: &SetupParameters, context: &ElectionContext, proof: &MaxBudgetRatedVotingProof) -> Result<(), VerificationError> {
+        verify_proof(params, context, proof)
+    }
+
+    fn verify_batch(params: &SetupParameters, ballots: &[(&ElectionContext, &MaxBudgetRatedVotingProof)]) -> Result<(), BatchVerificationError> {
+        verify_batch(params, ballots)
+    }
 }
 
+impl TallyScheme for MaxBudgetRatedVoting {
+    fn tally_setup(params: &SetupParameters) -> (PedersenGens, usize, i64) {
+        (params.pc_gens, params.ballot_size, 0)
+    }
 
-pub fn setup(max_credits: u64, ballot_size: usize, pc_gens: Option<PedersenGens>) -> Result<SetupParameters, String> {
+    fn setup_bytes(params: &SetupParameters) -> &[u8] {
+        &params.encoding
+    }
+
+    fn vote_bounds(params: &SetupParameters) -> (i128, i128) {
+        (0, params.max_per_candidate.as_ref().unwrap_or(&params.max_credit).value as i128)
+    }
+
+    fn vote_commitments(proof: &MaxBudgetRatedVotingProof) -> &[CompressedRistretto] {
+        &proof.votes_proof.1
+    }
+
+    fn generate_vote_with_openings<R: RngCore + CryptoRng>(params: &SetupParameters, context: &ElectionContext, ballot: &[u64], rng: &mut R) -> Result<(MaxBudgetRatedVotingProof, Vec<CandidateOpening>), BallotError> {
+        generate_vote_with_openings(params, context, ballot, rng)
+    }
+}
+
+
+pub fn setup(
+    max_credits: u64,
+    min_credits: Option<u64>,
+    max_per_candidate: Option<u64>,
+    ballot_size: usize,
+    pc_gens: Option<PedersenGens>
+) -> Result<SetupParameters, BallotError> {
     if !ballot_size.is_power_of_two() {
-        return Err("ballot_size must be a power of two".into());
+        return Err(BallotError::InvalidSetup("ballot_size must be a power of two".into()));
+    }
+
+    if max_per_candidate == Some(0) {
+        return Err(BallotError::InvalidSetup("max_per_candidate must be greater than zero".into()));
     }
 
-    let pc_gens: PedersenGens = pc_gens.unwrap_or_else(PedersenGens::default);
-    let bp_gens: BulletproofGens = BulletproofGens::new(8, ballot_size);
+    if min_credits.is_some_and(|min| min > max_credits) {
+        return Err(BallotError::InvalidSetup("min_credits must not exceed max_credits".into()));
+    }
+
+    let bit_size: usize = range_proof_bit_size(max_credits.max(max_per_candidate.unwrap_or(0)) as u128)?;
+    let pc_gens: PedersenGens = pc_gens.unwrap_or_default();
+    let bp_gens: BulletproofGens = BulletproofGens::new(bit_size, 2 * ballot_size);
     let max_credit: MaxCredit = MaxCredit::new(&pc_gens, max_credits);
+    let min_credit: Option<MaxCredit> = min_credits.map(|min| MaxCredit::new(&pc_gens, min));
+    let max_per_candidate: Option<MaxCredit> = max_per_candidate.map(|cap| MaxCredit::new(&pc_gens, cap));
 
-    Ok(SetupParameters {
+    let mut setup_params = SetupParameters {
         pc_gens,
         bp_gens,
+        bit_size,
         ballot_size,
-        max_credit
-    })
+        max_credit,
+        min_credit,
+        max_per_candidate,
+        encoding: Vec::new(),
+    };
+    setup_params.encoding = setup_params.encode();
+
+    Ok(setup_params)
 }
 
 pub fn generate_vote(
     setup_params: &SetupParameters,
-    ballot: Vec<u64>,
-) -> Result<MaxBudgetRatedVotingProof, String> {
-    if ballot.len() != setup_params.ballot_size {
-        return Err(format!(
-            "Ballot length {} does not match expected number of candidates {}",
-            ballot.len(),
-            setup_params.ballot_size
-        ));
-    }  
+    context: &ElectionContext,
+    ballot: &[u64],
+) -> Result<MaxBudgetRatedVotingProof, BallotError> {
+    generate_vote_with_rng(setup_params, context, ballot, &mut OsRng)
+}
 
-    let ballot_blindings: Vec<Scalar> = (0..ballot.len())
-        .map(|_| Scalar::random(&mut thread_rng()))
-        .collect();
+pub fn generate_vote_with_rng<R: RngCore + CryptoRng>(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[u64],
+    rng: &mut R,
+) -> Result<MaxBudgetRatedVotingProof, BallotError> {
+    generate_vote_with_openings(setup_params, context, ballot, rng).map(|(proof, _)| proof)
+}
 
-    let rangeproof_votes: (RangeProof, Vec<CompressedRistretto>) = RangeProof::prove_multiple(
-        &setup_params.bp_gens,
-        &setup_params.pc_gens,
-        &mut Transcript::new(b"max budget rated voting votes"),
-        &ballot,
-        &ballot_blindings,
-        8,
-    )
-    .map_err(|e| format!("Failed to create aggregated rangeproof: {:?}", e))?;
+pub fn generate_vote_with_openings<R: RngCore + CryptoRng>(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[u64],
+    rng: &mut R,
+) -> Result<(MaxBudgetRatedVotingProof, Vec<CandidateOpening>), BallotError> {
+    let (votes_proof, ballot_blindings) = prove_votes(setup_params, context, ballot, rng)?;
 
     let z: u64 = ballot
     .iter()
     .try_fold(0u64, |acc, &val| acc.checked_add(val))
-    .ok_or_else(|| "Overflow while summing ballot values".to_string())?;
+    .ok_or(BallotError::Overflow)?;
     let z_blinding: Scalar = ballot_blindings.iter().copied().reduce(|a, b| a + b).unwrap();
     let com_z: RistrettoPoint = setup_params.pc_gens.commit(Scalar::from(z), z_blinding);
 
-    let d = setup_params
-    .max_credit
-    .value
-    .checked_sub(z)
-    .ok_or_else(|| format!("Ballot sum {} exceeds max credit {}", z, setup_params.max_credit.value))?;
-    let d_blinding: Scalar = -z_blinding;
-    
-    let rangeproof_d: (RangeProof, CompressedRistretto) = RangeProof::prove_single(
+    // The lower bound is aggregated with `d`, so it only adds a few points to the proof.
+    let rangeproof_d: AggregatedRangeProof = prove_sum_bounds(
         &setup_params.bp_gens,
         &setup_params.pc_gens,
-        &mut Transcript::new(b"max budget rated voting"),
-        d,
-        &d_blinding,
-        8,
-    )
-    .map_err(|e| format!("Failed to create rangeproof: {:?}", e))?;
-
-    Ok(MaxBudgetRatedVotingProof {
-        votes_proof: rangeproof_votes, 
-        com_z: com_z, 
-        rangeproof_d: rangeproof_d
-    })
+        &mut context.scheme_transcript(b"max budget rated voting", &setup_params.encoding),
+        z,
+        z_blinding,
+        setup_params.min_credit.as_ref().map(|min| min.value),
+        Some(setup_params.max_credit.value),
+        setup_params.bit_size,
+        rng,
+    )?;
+    let openings: Vec<CandidateOpening> = openings(ballot, &ballot_blindings);
+
+    Ok((MaxBudgetRatedVotingProof {
+        votes_proof, 
+        com_z, 
+        rangeproof_d
+    }, openings))
 }
 
 pub fn verify_proof(
     setup_params: &SetupParameters,
-    validity_proof: MaxBudgetRatedVotingProof,
-) -> bool {
-    let vote_proof: RangeProof = validity_proof.votes_proof.0;
-    let vote_commitments: Vec<CompressedRistretto> = validity_proof.votes_proof.1;
+    context: &ElectionContext,
+    validity_proof: &MaxBudgetRatedVotingProof,
+) -> Result<(), VerificationError> {
+    let aggregated_commitment: RistrettoPoint = verify_votes(setup_params, context, &validity_proof.votes_proof)?.iter().sum();
 
-    if vote_proof
-        .verify_multiple(
-            &setup_params.bp_gens, 
-            &setup_params.pc_gens, 
-            &mut Transcript::new(b"max budget rated voting votes"), 
-            &vote_commitments, 
-            8
-        )
-        .is_err()
-    {
-        return false;
+    if aggregated_commitment != validity_proof.com_z {
+        return Err(VerificationError::CommitmentMismatch);
     }
 
-    let aggregated_commitment: RistrettoPoint = vote_commitments.iter()
-    .map(|c| c.decompress().unwrap())
-    .fold(RistrettoPoint::default(), |acc, commit| acc + commit);
+    verify_sum_bounds(
+        &setup_params.bp_gens,
+        &setup_params.pc_gens,
+        &mut context.scheme_transcript(b"max budget rated voting", &setup_params.encoding),
+        &validity_proof.com_z,
+        setup_params.min_credit.as_ref().map(|min| min.value),
+        Some(setup_params.max_credit.value),
+        &validity_proof.rangeproof_d,
+        setup_params.bit_size,
+    )
+}
 
-    if aggregated_commitment != validity_proof.com_z {
-        return false;
+/// Every check on a max-budget ballot is a range proof, so the ballots are
+/// verified individually.
+pub fn verify_batch(
+    setup_params: &SetupParameters,
+    ballots: &[(&ElectionContext, &MaxBudgetRatedVotingProof)],
+) -> Result<(), BatchVerificationError> {
+    verify_each(ballots, |context, proof| verify_proof(setup_params, context, proof))
+}
+
+/// Commits to every vote and range-proves it (and its distance to the
+/// per-candidate cap, if any). Returns the proof and the vote blindings.
+pub(super) fn prove_votes<R: RngCore + CryptoRng>(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[u64],
+    rng: &mut R,
+) -> Result<(VotesProof, Vec<Scalar>), BallotError> {
+    if ballot.len() != setup_params.ballot_size {
+        return Err(BallotError::LengthMismatch { expected: setup_params.ballot_size, actual: ballot.len() });
+    }  
+
+    let ballot_blindings: Vec<Scalar> = (0..ballot.len())
+        .map(|_| Scalar::random(rng))
+        .collect();
+
+    let mut values: Vec<u64> = ballot.to_vec();
+    let mut blindings: Vec<Scalar> = ballot_blindings.clone();
+
+    // With a per-candidate cap, cap - v is range-proven alongside every vote v.
+    if let Some(cap) = &setup_params.max_per_candidate {
+        for (index, &vote) in ballot.iter().enumerate() {
+            values.push(cap.value.checked_sub(vote).ok_or(BallotError::VoteOutOfRange { index })?);
+        }
+        blindings.extend(ballot_blindings.iter().map(|&r| -r));
     }
 
-    if validity_proof.rangeproof_d.1 != (setup_params.max_credit.commitment - validity_proof.com_z).compress() {
-        return false;
+    let (votes_rangeproof, mut vote_commitments): (RangeProof, Vec<CompressedRistretto>) = RangeProof::prove_multiple_with_rng(
+        &setup_params.bp_gens,
+        &setup_params.pc_gens,
+        &mut context.scheme_transcript(b"max budget rated voting votes", &setup_params.encoding),
+        &values,
+        &blindings,
+        setup_params.bit_size,
+        rng,
+    )
+    .map_err(BallotError::RangeProofFailed)?;
+    vote_commitments.truncate(ballot.len());
+
+    Ok(((votes_rangeproof, vote_commitments), ballot_blindings))
+}
+
+pub(super) fn openings(ballot: &[u64], blindings: &[Scalar]) -> Vec<CandidateOpening> {
+    ballot
+        .iter()
+        .zip(blindings)
+        .map(|(&value, &blinding)| CandidateOpening { value: value as i128, blinding })
+        .collect()
+}
+
+/// Verifies the range proof over the votes and returns the decompressed
+/// vote commitments for the caller's homomorphic sum check.
+pub(super) fn verify_votes(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    votes_proof: &VotesProof,
+) -> Result<Vec<RistrettoPoint>, VerificationError> {
+    let vote_proof: &RangeProof = &votes_proof.0;
+    let vote_commitments: &Vec<CompressedRistretto> = &votes_proof.1;
+
+    if vote_commitments.len() != setup_params.ballot_size {
+        return Err(VerificationError::LengthMismatch { expected: setup_params.ballot_size, actual: vote_commitments.len() });
     }
 
-    if validity_proof.rangeproof_d.0
-        .verify_single(&setup_params.bp_gens, &setup_params.pc_gens, &mut Transcript::new(b"max budget rated voting"), &validity_proof.rangeproof_d.1, 8)
-        .is_err()
-    {
-        return false;
+    let decompressed_votes: Vec<RistrettoPoint> = vote_commitments.iter()
+    .map(|c| c.decompress().ok_or(VerificationError::DecompressionFailed))
+    .collect::<Result<_, _>>()?;
+
+    let mut rangeproof_commitments: Vec<CompressedRistretto> = vote_commitments.clone();
+    if let Some(cap) = &setup_params.max_per_candidate {
+        rangeproof_commitments.extend(decompressed_votes.iter().map(|&v| (cap.commitment - v).compress()));
     }
 
-    true
+    vote_proof
+        .verify_multiple(
+            &setup_params.bp_gens, 
+            &setup_params.pc_gens, 
+            &mut context.scheme_transcript(b"max budget rated voting votes", &setup_params.encoding), 
+            &rangeproof_commitments, 
+            setup_params.bit_size
+        )
+        .map_err(VerificationError::RangeProofFailed)?;
+
+    Ok(decompressed_votes)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ballot_validation::election_context::test_context;
+    use rand::thread_rng;
 
     fn basic_setup(ballot_size: usize, max_credit: u64) -> SetupParameters {
-        setup(max_credit, ballot_size, None).unwrap()
+        setup(max_credit, None, None, ballot_size, None).unwrap()
     }
 
     #[test]
@@ -163,8 +375,8 @@ mod tests {
         let setup_params = basic_setup(4, 20);
         let ballot = vec![5, 3, 6, 6];
 
-        let proof = generate_vote(&setup_params, ballot).expect("Should generate proof");
-        assert!(verify_proof(&setup_params, proof), "Valid proof should verify");
+        let proof = generate_vote(&setup_params, &test_context(), &ballot).expect("Should generate proof");
+        assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok(), "Valid proof should verify");
     }
 
     #[test]
@@ -172,8 +384,8 @@ mod tests {
         let setup_params = basic_setup(4, 15);
         let ballot = vec![10, 5, 3, 0];
 
-        let result = generate_vote(&setup_params, ballot);
-        assert!(result.is_err(), "Should fail to generate proof for overspending ballot");
+        let result = generate_vote(&setup_params, &test_context(), &ballot);
+        assert_eq!(result.err(), Some(BallotError::BudgetExceeded { sum: 18, max: 15 }), "Should fail to generate proof for overspending ballot");
     }
 
     #[test]
@@ -181,11 +393,11 @@ mod tests {
         let setup_params = basic_setup(2, 10);
         let ballot = vec![5, 5];
 
-        let mut proof = generate_vote(&setup_params, ballot).expect("Should generate proof");
+        let mut proof = generate_vote(&setup_params, &test_context(), &ballot).expect("Should generate proof");
 
         proof.votes_proof.1[0] = setup_params.pc_gens.commit(Scalar::from(999u64), Scalar::zero()).compress();
 
-        assert!(!verify_proof(&setup_params, proof), "Tampered commitment should not verify");
+        assert!(matches!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::RangeProofFailed(_))), "Tampered commitment should not verify");
     }
 
     #[test]
@@ -193,8 +405,8 @@ mod tests {
         let setup_params = basic_setup(4, 10);
         let ballot = vec![3, 4];
 
-        let result = generate_vote(&setup_params, ballot);
-        assert!(result.is_err(), "Should fail when ballot length doesn't match setup");
+        let result = generate_vote(&setup_params, &test_context(), &ballot);
+        assert_eq!(result.err(), Some(BallotError::LengthMismatch { expected: 4, actual: 2 }), "Should fail when ballot length doesn't match setup");
     }
 
     #[test]
@@ -202,11 +414,171 @@ mod tests {
         let setup_params = basic_setup(2, 8);
         let ballot = vec![4, 4];
 
-        let proof = generate_vote(&setup_params, ballot).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), &ballot).unwrap();
         let expected_sum_commitment = proof.votes_proof.1.iter()
             .map(|c| c.decompress().unwrap())
             .fold(RistrettoPoint::default(), |acc, c| acc + c);
 
         assert_eq!(expected_sum_commitment, proof.com_z, "Aggregated commitment should match com_z");
     }
+
+    #[test]
+    fn test_setup_from_bytes_revalidates_parameters() {
+        let mut bytes = basic_setup(4, 20).to_bytes();
+        let ballot_size_offset = bytes.len() - 8;
+        bytes[ballot_size_offset..].copy_from_slice(&3u64.to_le_bytes());
+
+        let result = SetupParameters::from_bytes(&bytes);
+        assert!(matches!(result, Err(DecodingError::InvalidParameters(BallotError::InvalidSetup(_)))));
+    }
+
+    #[test]
+    fn test_bit_size_follows_max_credit() {
+        assert_eq!(basic_setup(2, 255).bit_size, 8);
+        assert_eq!(basic_setup(2, 256).bit_size, 16);
+        assert_eq!(basic_setup(2, 70_000).bit_size, 32);
+        assert_eq!(basic_setup(2, u64::MAX).bit_size, 64);
+    }
+
+    #[test]
+    fn test_budget_above_255_verifies() {
+        let setup_params = basic_setup(4, 1000);
+        let ballot = vec![600, 0, 300, 100];
+
+        let proof = generate_vote(&setup_params, &test_context(), &ballot).expect("Should generate proof");
+        assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok(), "Votes above 255 should verify with a wider range proof");
+
+        let overspending = vec![600, 0, 300, 101];
+        assert_eq!(generate_vote(&setup_params, &test_context(), &overspending).err(), Some(BallotError::BudgetExceeded { sum: 1001, max: 1000 }));
+    }
+
+    #[test]
+    fn test_per_candidate_cap() {
+        let setup_params = setup(40, None, Some(10), 4, None).unwrap();
+
+        let proof = generate_vote(&setup_params, &test_context(), &[10, 10, 10, 10]).expect("Should generate proof");
+        assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok(), "Ballot respecting the cap should verify");
+
+        let result = generate_vote(&setup_params, &test_context(), &[11, 0, 0, 0]);
+        assert_eq!(result.err(), Some(BallotError::VoteOutOfRange { index: 0 }), "Vote above the cap should be rejected");
+    }
+
+    #[test]
+    fn test_vote_above_cap_fails_verification() {
+        let setup_params = setup(40, None, Some(10), 2, None).unwrap();
+        let ballot = [25u64, 5];
+        let blindings: Vec<Scalar> = (0..2).map(|_| Scalar::random(&mut thread_rng())).collect();
+
+        // 10 - 25 has no range proof, so prove 0 in its place. The verifier
+        // derives the cap commitment from the vote and the proof fails.
+        let (range_proof, mut vote_commitments) = RangeProof::prove_multiple_with_rng(
+            &setup_params.bp_gens,
+            &setup_params.pc_gens,
+            &mut test_context().scheme_transcript(b"max budget rated voting votes", &setup_params.encoding),
+            &[25, 5, 0, 5],
+            &[blindings[0], blindings[1], -blindings[0], -blindings[1]],
+            setup_params.bit_size,
+            &mut thread_rng(),
+        ).unwrap();
+        vote_commitments.truncate(ballot.len());
+
+        // The budget part is honest: 30 of 40 credits.
+        let z_blinding: Scalar = blindings.iter().sum();
+        let rangeproof_d = prove_sum_bounds(
+            &setup_params.bp_gens,
+            &setup_params.pc_gens,
+            &mut test_context().scheme_transcript(b"max budget rated voting", &setup_params.encoding),
+            30,
+            z_blinding,
+            None,
+            Some(40),
+            setup_params.bit_size,
+            &mut thread_rng(),
+        ).unwrap();
+
+        let proof = MaxBudgetRatedVotingProof {
+            votes_proof: (range_proof, vote_commitments),
+            com_z: setup_params.pc_gens.commit(Scalar::from(30u64), z_blinding),
+            rangeproof_d,
+        };
+        assert!(
+            matches!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::RangeProofFailed(_))),
+            "A vote above the cap should not verify against capped parameters"
+        );
+    }
+
+    #[test]
+    fn test_zero_cap_should_fail() {
+        assert!(matches!(setup(40, None, Some(0), 2, None), Err(BallotError::InvalidSetup(_))));
+    }
+
+    #[test]
+    fn test_minimum_budget() {
+        let setup_params = setup(20, Some(10), None, 4, None).unwrap();
+
+        for ballot in [[4, 3, 2, 1], [5, 5, 5, 5]] {
+            let proof = generate_vote(&setup_params, &test_context(), &ballot).unwrap();
+            assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok());
+        }
+
+        assert_eq!(generate_vote(&setup_params, &test_context(), &[4, 3, 2, 0]).err(), Some(BallotError::BudgetBelowMinimum { sum: 9, min: 10 }));
+        assert_eq!(generate_vote(&setup_params, &test_context(), &[6, 5, 5, 5]).err(), Some(BallotError::BudgetExceeded { sum: 21, max: 20 }));
+    }
+
+    #[test]
+    fn test_proof_without_minimum_fails_minimum_verification() {
+        let unbounded = setup(20, None, None, 2, None).unwrap();
+        let bounded = setup(20, Some(10), None, 2, None).unwrap();
+
+        let blank_ballot = generate_vote(&unbounded, &test_context(), &[0, 0]).unwrap();
+        assert!(verify_proof(&unbounded, &test_context(), &blank_ballot).is_ok());
+        assert!(
+            matches!(verify_proof(&bounded, &test_context(), &blank_ballot), Err(VerificationError::RangeProofFailed(_))),
+            "A blank ballot should not verify against a minimum budget"
+        );
+    }
+
+    #[test]
+    fn test_minimum_above_maximum_should_fail() {
+        assert!(matches!(setup(10, Some(11), None, 2, None), Err(BallotError::InvalidSetup(_))));
+        assert!(setup(10, Some(10), None, 2, None).is_ok());
+    }
+
+    #[test]
+    fn test_proof_is_bound_to_context_and_setup() {
+        let setup_params = setup(10, None, None, 4, None).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), &[4, 0, 3, 3]).unwrap();
+
+        for context in [
+            ElectionContext::new(b"test election", b"other contest", b"test voter"),
+            ElectionContext::new(b"test election", b"test contest", b"other voter"),
+        ] {
+            assert!(matches!(verify_proof(&setup_params, &context, &proof), Err(VerificationError::RangeProofFailed(_))));
+        }
+
+        let other = setup(11, None, None, 4, None).unwrap();
+        assert!(matches!(verify_proof(&other, &test_context(), &proof), Err(VerificationError::RangeProofFailed(_))));
+    }
+
+    #[test]
+    fn test_batch_verification() {
+        let setup_params = setup(10, None, None, 4, None).unwrap();
+        let contexts: Vec<ElectionContext> = (0..2)
+            .map(|voter| ElectionContext::new(b"test election", b"test contest", &[voter]))
+            .collect();
+        let proofs: Vec<MaxBudgetRatedVotingProof> = contexts
+            .iter()
+            .map(|context| generate_vote(&setup_params, context, &[4, 0, 3, 3]).unwrap())
+            .collect();
+
+        let batch: Vec<(&ElectionContext, &MaxBudgetRatedVotingProof)> = contexts.iter().zip(&proofs).collect();
+        assert!(verify_batch(&setup_params, &batch).is_ok());
+        assert!(verify_batch(&setup_params, &[]).is_ok());
+
+        // The second and fourth ballots are replayed under the other voter's context.
+        let batch = [(&contexts[0], &proofs[0]), (&contexts[0], &proofs[1]), (&contexts[1], &proofs[1]), (&contexts[1], &proofs[0])];
+        let invalid = verify_batch(&setup_params, &batch).unwrap_err().invalid;
+        assert_eq!(invalid.iter().map(|(index, _)| *index).collect::<Vec<usize>>(), vec![1, 3]);
+        assert!(invalid.iter().all(|(_, error)| matches!(error, VerificationError::RangeProofFailed(_))));
+    }
 }
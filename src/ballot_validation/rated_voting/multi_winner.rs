@@ -0,0 +1,228 @@
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+use bulletproofs::PedersenGens;
+use crate::ballot_validation::ballot_scheme::BallotScheme;
+use crate::ballot_validation::election_context::ElectionContext;
+use crate::ballot_validation::encoding::{impl_serde_via_bytes, ObjectKind, Reader, SchemeTag, Writer};
+use crate::ballot_validation::errors::{BallotError, BatchVerificationError, DecodingError, VerificationError};
+use super::approval::{self, ApprovalVotingProof};
+
+/// Setup for "choose between `min_k` and `max_k` of n" ballots. This is
+/// approval voting with both limits required, so ballots are proven and
+/// verified by `approval`.
+pub struct SetupParameters {
+    inner: approval::SetupParameters,
+}
+
+#[derive(Clone)]
+pub struct MultiWinnerVotingProof {
+    inner: ApprovalVotingProof,
+}
+
+impl SetupParameters {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::MultiWinnerVoting, ObjectKind::SetupParameters);
+        writer
+            .pc_gens(&self.inner.pc_gens)
+            .u64(self.inner.ballot_size as u64)
+            .u64(self.inner.min_approvals.unwrap_or(0))
+            .u64(self.inner.max_approvals.unwrap_or(self.inner.ballot_size as u64));
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SetupParameters, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::MultiWinnerVoting, ObjectKind::SetupParameters)?;
+        let pc_gens = reader.pc_gens()?;
+        let ballot_size = reader.usize()?;
+        let min_k = reader.u64()?;
+        let max_k = reader.u64()?;
+        reader.finish()?;
+
+        setup(ballot_size, min_k, max_k, Some(pc_gens)).map_err(DecodingError::InvalidParameters)
+    }
+}
+
+impl MultiWinnerVotingProof {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::MultiWinnerVoting, ObjectKind::Proof);
+        self.inner.write(&mut writer);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<MultiWinnerVotingProof, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::MultiWinnerVoting, ObjectKind::Proof)?;
+        let inner = ApprovalVotingProof::read(&mut reader)?;
+        reader.finish()?;
+
+        Ok(MultiWinnerVotingProof { inner })
+    }
+}
+
+impl_serde_via_bytes!(SetupParameters, "multi-winner voting setup");
+impl_serde_via_bytes!(MultiWinnerVotingProof, "multi-winner voting proof");
+
+pub struct MultiWinnerVoting;
+
+impl BallotScheme for MultiWinnerVoting {
+    type Params = SetupParameters;
+    type Ballot = [bool];
+    type Proof = MultiWinnerVotingProof;
+    type Error = BallotError;
+
+    fn generate_vote_with_rng<R: RngCore + CryptoRng>(params: &SetupParameters, context: &ElectionContext, ballot: &[bool], rng: &mut R) -> Result<MultiWinnerVotingProof, BallotError> {
+        generate_vote_with_rng(params, context, ballot, rng)
+    }
+
+    fn verify_proof(params: &SetupParameters, context: &ElectionContext, proof: &MultiWinnerVotingProof) -> Result<(), VerificationError> {
+        verify_proof(params, context, proof)
+    }
+
+    fn verify_batch(params: &SetupParameters, ballots: &[(&ElectionContext, &MultiWinnerVotingProof)]) -> Result<(), BatchVerificationError> {
+        verify_batch(params, ballots)
+    }
+}
+
+pub fn setup(
+    ballot_size: usize,
+    min_k: u64,
+    max_k: u64,
+    pc_gens: Option<PedersenGens>
+) -> Result<SetupParameters, BallotError> {
+    if max_k == 0 {
+        return Err(BallotError::InvalidSetup("max_k must be greater than zero".into()));
+    }
+
+    Ok(SetupParameters {
+        inner: approval::setup(ballot_size, Some(min_k), Some(max_k), pc_gens)?,
+    })
+}
+
+pub fn generate_vote(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[bool],
+) -> Result<MultiWinnerVotingProof, BallotError> {
+    generate_vote_with_rng(setup_params, context, ballot, &mut OsRng)
+}
+
+pub fn generate_vote_with_rng<R: RngCore + CryptoRng>(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[bool],
+    rng: &mut R,
+) -> Result<MultiWinnerVotingProof, BallotError> {
+    let inner = approval::generate_vote_with_rng(&setup_params.inner, context, ballot, rng)?;
+    Ok(MultiWinnerVotingProof { inner })
+}
+
+pub fn verify_proof(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    validity_proof: &MultiWinnerVotingProof,
+) -> Result<(), VerificationError> {
+    approval::verify_proof(&setup_params.inner, context, &validity_proof.inner)
+}
+
+pub fn verify_batch(
+    setup_params: &SetupParameters,
+    ballots: &[(&ElectionContext, &MultiWinnerVotingProof)],
+) -> Result<(), BatchVerificationError> {
+    let ballots: Vec<(&ElectionContext, &ApprovalVotingProof)> = ballots.iter().map(|&(context, proof)| (context, &proof.inner)).collect();
+    approval::verify_batch(&setup_params.inner, &ballots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ballot_validation::election_context::test_context;
+
+    #[test]
+    fn test_up_to_k_selections_verify() {
+        let setup_params = setup(5, 0, 3, None).unwrap();
+
+        for ballot in [[false; 5], [true, false, false, false, false], [true, false, true, false, true]] {
+            let proof = generate_vote(&setup_params, &test_context(), &ballot).expect("Should generate proof");
+            assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok(), "Ballot with at most k selections should verify");
+        }
+    }
+
+    #[test]
+    fn test_selection_count_out_of_range() {
+        let setup_params = setup(5, 2, 3, None).unwrap();
+        assert_eq!(generate_vote(&setup_params, &test_context(), &[true, false, false, false, false]).err(), Some(BallotError::BudgetBelowMinimum { sum: 1, min: 2 }));
+        assert_eq!(generate_vote(&setup_params, &test_context(), &[true, true, true, true, false]).err(), Some(BallotError::BudgetExceeded { sum: 4, max: 3 }));
+    }
+
+    #[test]
+    fn test_selections_proof_is_tied_to_the_ballot() {
+        let setup_params = setup(4, 1, 2, None).unwrap();
+        let mut proof = generate_vote(&setup_params, &test_context(), &[true, true, false, false]).unwrap();
+        let other = generate_vote(&setup_params, &test_context(), &[false, false, true, false]).unwrap();
+
+        proof.inner.approvals_proof = other.inner.approvals_proof.clone();
+        assert_eq!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::CommitmentMismatch));
+
+        proof.inner.approvals_proof = None;
+        assert_eq!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::LengthMismatch { expected: 2, actual: 0 }));
+    }
+
+    #[test]
+    fn test_invalid_setup() {
+        assert!(matches!(setup(4, 0, 0, None), Err(BallotError::InvalidSetup(_))));
+        assert!(matches!(setup(4, 0, 5, None), Err(BallotError::InvalidSetup(_))));
+        assert!(matches!(setup(4, 3, 2, None), Err(BallotError::InvalidSetup(_))));
+        assert!(setup(4, 4, 4, None).is_ok());
+    }
+
+    #[test]
+    fn test_encoding_is_tagged_as_multi_winner() {
+        let setup_params = setup(4, 1, 2, None).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), &[true, false, true, false]).unwrap();
+
+        let decoded = SetupParameters::from_bytes(&setup_params.to_bytes()).unwrap();
+        let proof = MultiWinnerVotingProof::from_bytes(&proof.to_bytes()).unwrap();
+        assert!(verify_proof(&decoded, &test_context(), &proof).is_ok());
+
+        assert_eq!(approval::SetupParameters::from_bytes(&setup_params.to_bytes()).err(), Some(DecodingError::UnexpectedTag));
+        assert_eq!(ApprovalVotingProof::from_bytes(&proof.to_bytes()).err(), Some(DecodingError::UnexpectedTag));
+    }
+
+    #[test]
+    fn test_proof_is_bound_to_context_and_setup() {
+        let setup_params = setup(4, 1, 2, None).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), &[true, false, true, false]).unwrap();
+
+        for context in [
+            ElectionContext::new(b"test election", b"other contest", b"test voter"),
+            ElectionContext::new(b"test election", b"test contest", b"other voter"),
+        ] {
+            assert_eq!(verify_proof(&setup_params, &context, &proof), Err(VerificationError::BitProofFailed { index: 0 }));
+        }
+
+        let other = setup(4, 1, 3, None).unwrap();
+        assert_eq!(verify_proof(&other, &test_context(), &proof), Err(VerificationError::BitProofFailed { index: 0 }));
+    }
+
+    #[test]
+    fn test_batch_verification() {
+        let setup_params = setup(4, 1, 2, None).unwrap();
+        let contexts: Vec<ElectionContext> = (0..2)
+            .map(|voter| ElectionContext::new(b"test election", b"test contest", &[voter]))
+            .collect();
+        let proofs: Vec<MultiWinnerVotingProof> = contexts
+            .iter()
+            .map(|context| generate_vote(&setup_params, context, &[true, false, true, false]).unwrap())
+            .collect();
+
+        let batch: Vec<(&ElectionContext, &MultiWinnerVotingProof)> = contexts.iter().zip(&proofs).collect();
+        assert!(verify_batch(&setup_params, &batch).is_ok());
+        assert!(verify_batch(&setup_params, &[]).is_ok());
+
+        // The second and fourth ballots are replayed under the other voter's
+        // context. Bit proofs are deferred, so the selections range proof,
+        // checked per ballot, is what reports them.
+        let batch = [(&contexts[0], &proofs[0]), (&contexts[0], &proofs[1]), (&contexts[1], &proofs[1]), (&contexts[1], &proofs[0])];
+        let invalid = verify_batch(&setup_params, &batch).unwrap_err().invalid;
+        assert_eq!(invalid.iter().map(|(index, _)| *index).collect::<Vec<usize>>(), vec![1, 3]);
+        assert!(invalid.iter().all(|(_, error)| matches!(error, VerificationError::RangeProofFailed(_))));
+    }
+}
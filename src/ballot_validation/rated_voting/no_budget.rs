@@ -1,13 +1,21 @@
-use rand::thread_rng;
-use merlin::Transcript;
+use rand::{rngs::OsRng, CryptoRng, RngCore};
 use curve25519_dalek_ng::{ristretto::{CompressedRistretto, RistrettoPoint}, scalar::Scalar};
 use bulletproofs::{BulletproofGens, PedersenGens, RangeProof};
+use crate::ballot_validation::ballot_scheme::{verify_each, BallotScheme};
+use crate::ballot_validation::election_context::ElectionContext;
+use crate::ballot_validation::encoding::{impl_serde_via_bytes, ObjectKind, Reader, SchemeTag, Writer};
+use crate::ballot_validation::errors::{BallotError, BatchVerificationError, DecodingError, VerificationError};
+use crate::ballot_validation::tally::{CandidateOpening, TallyScheme};
+use super::common::range_proof_bit_size;
 
 pub struct SetupParameters {
     pc_gens: PedersenGens,
     bp_gens: BulletproofGens,
+    bit_size: usize,
     range: (i64, i64),
-    shifted_upperbound: RistrettoPoint
+    ballot_size: usize,
+    shifted_upperbound: RistrettoPoint,
+    encoding: Vec<u8>,
 }
 
 #[derive(Clone)] 
@@ -16,106 +24,259 @@ pub struct NoBudgetRatedVotingProof {
     aggregated_rangeproof: (RangeProof, Vec<CompressedRistretto>)
 }
 
-pub fn setup(range: (i64, i64), ballot_size: usize, pc_gens: Option<PedersenGens>) -> Result<SetupParameters, String> {
+impl SetupParameters {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encoding.clone()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::NoBudgetRatedVoting, ObjectKind::SetupParameters);
+        writer
+            .pc_gens(&self.pc_gens)
+            .i64(self.range.0)
+            .i64(self.range.1)
+            .u64(self.ballot_size as u64);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SetupParameters, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::NoBudgetRatedVoting, ObjectKind::SetupParameters)?;
+        let pc_gens = reader.pc_gens()?;
+        let range = (reader.i64()?, reader.i64()?);
+        let ballot_size = reader.usize()?;
+        reader.finish()?;
+
+        setup(range, ballot_size, Some(pc_gens)).map_err(DecodingError::InvalidParameters)
+    }
+}
+
+impl NoBudgetRatedVotingProof {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::NoBudgetRatedVoting, ObjectKind::Proof);
+        writer
+            .points(&self.shifted_ballot_committments)
+            .range_proof(&self.aggregated_rangeproof.0)
+            .points(&self.aggregated_rangeproof.1);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<NoBudgetRatedVotingProof, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::NoBudgetRatedVoting, ObjectKind::Proof)?;
+        let shifted_ballot_committments = reader.points()?;
+        let aggregated_rangeproof = (reader.range_proof()?, reader.points()?);
+        reader.finish()?;
+
+        Ok(NoBudgetRatedVotingProof {
+            shifted_ballot_committments,
+            aggregated_rangeproof
+        })
+    }
+}
+
+impl_serde_via_bytes!(SetupParameters, "no budget rated voting setup");
+impl_serde_via_bytes!(NoBudgetRatedVotingProof, "no budget rated voting proof");
+
+pub struct NoBudgetRatedVoting;
+
+impl BallotScheme for NoBudgetRatedVoting {
+    type Params = SetupParameters;
+    type Ballot = [i64];
+    type Proof = NoBudgetRatedVotingProof;
+    type Error = BallotError;
+
+    fn generate_vote_with_rng<R: RngCore + CryptoRng>(params: &SetupParameters, context: &ElectionContext, ballot: &[i64], rng: &mut R) -> Result<NoBudgetRatedVotingProof, BallotError> {
+        generate_vote_with_rng(params, context, ballot, rng)
+    }
+
+    fn verify_proof(params: &SetupParameters, context: &ElectionContext, proof: &NoBudgetRatedVotingProof) -> Result<(), VerificationError> {
+        verify_proof(params, context, proof)
+    }
+
+    fn verify_batch(params: &SetupParameters, ballots: &[(&ElectionContext, &NoBudgetRatedVotingProof)]) -> Result<(), BatchVerificationError> {
+        verify_batch(params, ballots)
+    }
+}
+
+impl TallyScheme for NoBudgetRatedVoting {
+    fn tally_setup(params: &SetupParameters) -> (PedersenGens, usize, i64) {
+        (params.pc_gens, params.ballot_size, params.range.1)
+    }
+
+    fn setup_bytes(params: &SetupParameters) -> &[u8] {
+        &params.encoding
+    }
+
+    fn vote_bounds(params: &SetupParameters) -> (i128, i128) {
+        (params.range.0 as i128, params.range.1 as i128)
+    }
+
+    fn vote_commitments(proof: &NoBudgetRatedVotingProof) -> &[CompressedRistretto] {
+        &proof.shifted_ballot_committments
+    }
+
+    fn generate_vote_with_openings<R: RngCore + CryptoRng>(params: &SetupParameters, context: &ElectionContext, ballot: &[i64], rng: &mut R) -> Result<(NoBudgetRatedVotingProof, Vec<CandidateOpening>), BallotError> {
+        generate_vote_with_openings(params, context, ballot, rng)
+    }
+}
+
+pub fn setup(range: (i64, i64), ballot_size: usize, pc_gens: Option<PedersenGens>) -> Result<SetupParameters, BallotError> {
     if !ballot_size.is_power_of_two() {
-        return Err("ballot_size must be a power of two".into());
+        return Err(BallotError::InvalidSetup("ballot_size must be a power of two".into()));
     }
 
     if range.0 >= range.1 {
-        return Err("range must be in the form (-N, N) with N > 0".into());
+        return Err(BallotError::InvalidSetup("range must be in the form (-N, N) with N > 0".into()));
     }
 
     if range.0 != -range.1 {
-        return Err("range must be symmetric: lower = -upper".into());
+        return Err(BallotError::InvalidSetup("range must be symmetric: lower = -upper".into()));
     }
 
-    let pc_gens: PedersenGens = pc_gens.unwrap_or_else(PedersenGens::default);
+    let bit_size: usize = range_proof_bit_size(range.1 as u128 * 2)?;
+    let pc_gens: PedersenGens = pc_gens.unwrap_or_default();
     let shifted_upperbound = pc_gens.commit(Scalar::from(range.1 as u64 * 2), Scalar::zero());
     
-    Ok(SetupParameters {
+    let mut setup_params = SetupParameters {
         pc_gens,
-        bp_gens: BulletproofGens::new(8, ballot_size),
+        bp_gens: BulletproofGens::new(bit_size, 2 * ballot_size),
+        bit_size,
         range,
-        shifted_upperbound
-    })
+        ballot_size,
+        shifted_upperbound,
+        encoding: Vec::new(),
+    };
+    setup_params.encoding = setup_params.encode();
+
+    Ok(setup_params)
 }
 
 pub fn generate_vote(
-    ballot: Vec<i64>,
-    setup_params: &SetupParameters
-) -> Result<NoBudgetRatedVotingProof, String> {
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[i64],
+) -> Result<NoBudgetRatedVotingProof, BallotError> {
+    generate_vote_with_rng(setup_params, context, ballot, &mut OsRng)
+}
+
+pub fn generate_vote_with_rng<R: RngCore + CryptoRng>(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[i64],
+    rng: &mut R,
+) -> Result<NoBudgetRatedVotingProof, BallotError> {
+    generate_vote_with_openings(setup_params, context, ballot, rng).map(|(proof, _)| proof)
+}
+
+/// Openings are of the unshifted votes; the tally adds the offset back.
+pub fn generate_vote_with_openings<R: RngCore + CryptoRng>(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[i64],
+    rng: &mut R,
+) -> Result<(NoBudgetRatedVotingProof, Vec<CandidateOpening>), BallotError> {
+    if ballot.len() != setup_params.ballot_size {
+        return Err(BallotError::LengthMismatch { expected: setup_params.ballot_size, actual: ballot.len() });
+    }
+
     let range_upperbound: i64 = setup_params.range.1;
     let shifted_range_upperbound: u64 = range_upperbound as u64 * 2;
-    let shifted_ballot: Vec<u64> = ballot.iter().map(|&v| shift_vote(v, range_upperbound)).collect::<Result<_, _>>()?;
-
-    let blindings: Vec<Scalar> = (0..ballot.len()).map(|_| Scalar::random(&mut thread_rng())).collect();
-    let shifted_ballot_committments : Vec<CompressedRistretto> = shifted_ballot
+    let shifted_ballot: Vec<u64> = ballot
         .iter()
-        .zip(blindings.iter())
-        .map(|(&v, &blinding)| setup_params.pc_gens.commit(Scalar::from(v), blinding).compress())
-        .collect();
+        .enumerate()
+        .map(|(index, &v)| shift_vote(v, range_upperbound, index))
+        .collect::<Result<_, _>>()?;
+
+    let blindings: Vec<Scalar> = (0..ballot.len()).map(|_| Scalar::random(rng)).collect();
 
     let d_values: Vec<u64> = shifted_ballot
     .iter()
-    .map(|&v| shifted_range_upperbound.checked_sub(v).ok_or("Vote is outside range!".to_string()))
-    .collect::<Result<_, _>>()?;
+    .map(|&v| shifted_range_upperbound - v)
+    .collect();
     let d_blindings: Vec<Scalar> = blindings.iter().map(|&r| -r).collect();
+    let openings: Vec<CandidateOpening> = ballot
+        .iter()
+        .zip(&blindings)
+        .map(|(&value, &blinding)| CandidateOpening { value: value as i128, blinding })
+        .collect();
 
-    let aggregated_rangeproof: (RangeProof, Vec<CompressedRistretto>) = RangeProof::prove_multiple(
+    // Both v and 2N - v are range-proven in one aggregated proof, so 0 <= v <= 2N.
+    let (rangeproof, mut commitments): (RangeProof, Vec<CompressedRistretto>) = RangeProof::prove_multiple_with_rng(
         &setup_params.bp_gens,
         &setup_params.pc_gens,
-        &mut Transcript::new(b"no budget rated voting"),
-        &d_values,
-        &d_blindings,
-        8,
+        &mut context.scheme_transcript(b"no budget rated voting", &setup_params.encoding),
+        &[shifted_ballot, d_values].concat(),
+        &[blindings, d_blindings].concat(),
+        setup_params.bit_size,
+        rng,
     )
-    .expect("Failed to create aggregated rangeproof");
+    .map_err(BallotError::RangeProofFailed)?;
+    let d_commitments: Vec<CompressedRistretto> = commitments.split_off(ballot.len());
 
-    Ok(NoBudgetRatedVotingProof {
-        shifted_ballot_committments,
-        aggregated_rangeproof
-    })
+    Ok((NoBudgetRatedVotingProof {
+        shifted_ballot_committments: commitments,
+        aggregated_rangeproof: (rangeproof, d_commitments)
+    }, openings))
 }
 
 pub fn verify_proof(
     setup_params: &SetupParameters,
-    validity_proof: NoBudgetRatedVotingProof
-) -> bool {
-    let d_commitments: Vec<CompressedRistretto> = validity_proof.aggregated_rangeproof.1;
-    let d_rangeproof = validity_proof.aggregated_rangeproof.0;
+    context: &ElectionContext,
+    validity_proof: &NoBudgetRatedVotingProof
+) -> Result<(), VerificationError> {
+    let d_commitments: &Vec<CompressedRistretto> = &validity_proof.aggregated_rangeproof.1;
+    let d_rangeproof: &RangeProof = &validity_proof.aggregated_rangeproof.0;
 
-    if d_rangeproof
-        .verify_multiple(
-            &setup_params.bp_gens, 
-            &setup_params.pc_gens, 
-            &mut Transcript::new(b"no budget rated voting"), 
-            &d_commitments, 
-            8
-        )
-        .is_err()
-    {
-        return false;
+    for commitments in [d_commitments, &validity_proof.shifted_ballot_committments] {
+        if commitments.len() != setup_params.ballot_size {
+            return Err(VerificationError::LengthMismatch { expected: setup_params.ballot_size, actual: commitments.len() });
+        }
     }
 
     for (d_commitment, v_commitment) in d_commitments.iter().zip(validity_proof.shifted_ballot_committments.iter()) {
-        if *d_commitment != (setup_params.shifted_upperbound - v_commitment.decompress().unwrap()).compress() {
-            return false;
+        let v_commitment: RistrettoPoint = v_commitment.decompress().ok_or(VerificationError::DecompressionFailed)?;
+        if *d_commitment != (setup_params.shifted_upperbound - v_commitment).compress() {
+            return Err(VerificationError::CommitmentMismatch);
         }
     }
 
-    true
+    d_rangeproof
+        .verify_multiple(
+            &setup_params.bp_gens, 
+            &setup_params.pc_gens, 
+            &mut context.scheme_transcript(b"no budget rated voting", &setup_params.encoding), 
+            &[validity_proof.shifted_ballot_committments.as_slice(), d_commitments].concat(), 
+            setup_params.bit_size
+        )
+        .map_err(VerificationError::RangeProofFailed)
+}
+
+/// A no-budget ballot is a single aggregated range proof, so the ballots are
+/// verified individually.
+pub fn verify_batch(
+    setup_params: &SetupParameters,
+    ballots: &[(&ElectionContext, &NoBudgetRatedVotingProof)],
+) -> Result<(), BatchVerificationError> {
+    verify_each(ballots, |context, proof| verify_proof(setup_params, context, proof))
 }
 
-fn shift_vote(value: i64, range_upperbound: i64) -> Result<u64, String> {
-    value.checked_add(range_upperbound)
-        .ok_or_else(|| format!("Overflow when shifting vote: {} + {}", value, range_upperbound))?
+fn shift_vote(value: i64, range_upperbound: i64, index: usize) -> Result<u64, BallotError> {
+    let shifted: u64 = value.checked_add(range_upperbound)
+        .ok_or(BallotError::Overflow)?
         .try_into()
-        .map_err(|_| format!("Shifted value out of u64 range: {}", value + range_upperbound))
+        .map_err(|_| BallotError::VoteOutOfRange { index })?;
+
+    if shifted > range_upperbound as u64 * 2 {
+        return Err(BallotError::VoteOutOfRange { index });
+    }
+
+    Ok(shifted)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ballot_validation::election_context::test_context;
+    use rand::thread_rng;
     use curve25519_dalek_ng::scalar::Scalar;
 
     fn basic_setup(ballot_size: usize) -> SetupParameters {
@@ -128,20 +289,20 @@ mod tests {
         let setup_params = basic_setup(4);
         let ballot = vec![0, -5, 7, 10];
 
-        let proof = generate_vote(ballot, &setup_params).expect("Proof generation failed");
-        assert!(verify_proof(&setup_params, proof), "Proof verification failed for valid input");
+        let proof = generate_vote(&setup_params, &test_context(), &ballot).expect("Proof generation failed");
+        assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok(), "Proof verification failed for valid input");
     }
 
     #[test]
     fn test_invalid_proof_verification_wrong_commitments() {
         let setup_params = basic_setup(2);
         let ballot = vec![3, -2];
-        let mut proof = generate_vote(ballot, &setup_params).expect("Proof generation failed");
+        let mut proof = generate_vote(&setup_params, &test_context(), &ballot).expect("Proof generation failed");
 
         // Tamper with one of the commitments
         proof.shifted_ballot_committments[0] = setup_params.pc_gens.commit(Scalar::from(999u64), Scalar::random(&mut thread_rng())).compress();
 
-        assert!(!verify_proof(&setup_params, proof), "Tampered proof should not verify");
+        assert_eq!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::CommitmentMismatch), "Tampered proof should not verify");
     }
 
     #[test]
@@ -149,19 +310,146 @@ mod tests {
         let setup_params = basic_setup(2);
         let ballot = vec![15, -12]; // Out of range since range is -10 to 10
 
-        let result = generate_vote(ballot, &setup_params);
-        assert!(result.is_err(), "Should not generate proof for out-of-range vote");
+        let result = generate_vote(&setup_params, &test_context(), &ballot);
+        assert_eq!(result.err(), Some(BallotError::VoteOutOfRange { index: 0 }), "Should not generate proof for out-of-range vote");
     }
 
     #[test]
     fn test_non_power_of_two_ballot_size() {
         let result = setup((-10, 10), 3, None);
-        assert!(result.is_err(), "Ballot size not power of two should fail");
+        assert!(matches!(result, Err(BallotError::InvalidSetup(_))), "Ballot size not power of two should fail");
     }
 
     #[test]
     fn test_asymmetric_range_should_fail() {
         let result = setup((-10, 8), 2, None);
-        assert!(result.is_err(), "Asymmetric range should be rejected");
+        assert!(matches!(result, Err(BallotError::InvalidSetup(_))), "Asymmetric range should be rejected");
+    }
+
+    #[test]
+    fn test_mismatched_ballot_length() {
+        let setup_params = basic_setup(4);
+        let ballot = vec![1, 2];
+
+        let result = generate_vote(&setup_params, &test_context(), &ballot);
+        assert_eq!(result.err(), Some(BallotError::LengthMismatch { expected: 4, actual: 2 }));
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_malformed_input() {
+        let setup_params = basic_setup(2);
+        let proof = generate_vote(&setup_params, &test_context(), &[1, 2]).unwrap();
+        let bytes = proof.to_bytes();
+
+        assert_eq!(NoBudgetRatedVotingProof::from_bytes(&bytes[..bytes.len() - 1]).err(), Some(DecodingError::UnexpectedEnd));
+        assert_eq!(NoBudgetRatedVotingProof::from_bytes(&setup_params.to_bytes()).err(), Some(DecodingError::UnexpectedTag));
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = 0xff;
+        assert_eq!(NoBudgetRatedVotingProof::from_bytes(&wrong_version).err(), Some(DecodingError::UnsupportedVersion(0xff)));
+    }
+
+    #[test]
+    fn test_bit_size_follows_range() {
+        assert_eq!(setup((-127, 127), 2, None).unwrap().bit_size, 8);
+        assert_eq!(setup((-128, 128), 2, None).unwrap().bit_size, 16);
+        assert_eq!(setup((-i64::MAX, i64::MAX), 2, None).unwrap().bit_size, 64);
+    }
+
+    #[test]
+    fn test_wide_range_verifies() {
+        let setup_params = setup((-1000, 1000), 4, None).unwrap();
+        let ballot = vec![-1000, 999, 0, 1000];
+
+        let proof = generate_vote(&setup_params, &test_context(), &ballot).expect("Proof generation failed");
+        assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok(), "Votes outside ±127 should verify with a wider range proof");
+    }
+
+    // Builds a correctly shaped proof that commits to `shifted_votes` but
+    // range-proves `proven_votes` in their place, as a prover must for a
+    // shifted vote below zero (a rating below -N).
+    fn forge_proof(setup_params: &SetupParameters, shifted_votes: &[Scalar], proven_votes: &[u64]) -> NoBudgetRatedVotingProof {
+        let shifted_range_upperbound = Scalar::from(setup_params.range.1 as u64 * 2);
+        let blindings: Vec<Scalar> = shifted_votes.iter().map(|_| Scalar::random(&mut thread_rng())).collect();
+
+        let shifted_ballot_committments: Vec<CompressedRistretto> = shifted_votes
+            .iter()
+            .zip(blindings.iter())
+            .map(|(&v, &blinding)| setup_params.pc_gens.commit(v, blinding).compress())
+            .collect();
+        let d_values: Vec<u64> = shifted_votes
+            .iter()
+            .map(|&v| u64::from_le_bytes((shifted_range_upperbound - v).as_bytes()[..8].try_into().unwrap()))
+            .collect();
+        let d_blindings: Vec<Scalar> = blindings.iter().map(|&r| -r).collect();
+
+        let (rangeproof, mut commitments) = RangeProof::prove_multiple(
+            &setup_params.bp_gens,
+            &setup_params.pc_gens,
+            &mut test_context().scheme_transcript(b"no budget rated voting", &setup_params.encoding),
+            &[proven_votes, &d_values].concat(),
+            &[blindings, d_blindings].concat(),
+            setup_params.bit_size,
+        )
+        .unwrap();
+        let d_commitments: Vec<CompressedRistretto> = commitments.split_off(shifted_votes.len());
+
+        NoBudgetRatedVotingProof {
+            shifted_ballot_committments,
+            aggregated_rangeproof: (rangeproof, d_commitments)
+        }
+    }
+
+    #[test]
+    fn test_vote_below_lower_bound_is_rejected() {
+        let setup_params = basic_setup(2);
+
+        // Proving the committed votes themselves gives a valid ballot.
+        let proof = forge_proof(&setup_params, &[Scalar::from(5u64), Scalar::zero()], &[5, 0]);
+        assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok());
+
+        // Shifted votes of -5 and 0, i.e. ratings of -15 and -10 with range (-10, 10).
+        // Every d = 2N - v is in range and matches its vote commitment, so only
+        // the range proof on the votes can reject the ballot.
+        let proof = forge_proof(&setup_params, &[-Scalar::from(5u64), Scalar::zero()], &[0, 0]);
+        assert!(
+            matches!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::RangeProofFailed(_))),
+            "Votes below the lower bound should not verify"
+        );
+    }
+
+    #[test]
+    fn test_proof_is_bound_to_context() {
+        let setup_params = setup((-5, 5), 4, None).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), &[-5, 0, 2, 5]).unwrap();
+
+        for context in [
+            ElectionContext::new(b"test election", b"other contest", b"test voter"),
+            ElectionContext::new(b"test election", b"test contest", b"other voter"),
+        ] {
+            assert!(matches!(verify_proof(&setup_params, &context, &proof), Err(VerificationError::RangeProofFailed(_))));
+        }
+    }
+
+    #[test]
+    fn test_batch_verification() {
+        let setup_params = setup((-5, 5), 4, None).unwrap();
+        let contexts: Vec<ElectionContext> = (0..2)
+            .map(|voter| ElectionContext::new(b"test election", b"test contest", &[voter]))
+            .collect();
+        let proofs: Vec<NoBudgetRatedVotingProof> = contexts
+            .iter()
+            .map(|context| generate_vote(&setup_params, context, &[-5, 0, 2, 5]).unwrap())
+            .collect();
+
+        let batch: Vec<(&ElectionContext, &NoBudgetRatedVotingProof)> = contexts.iter().zip(&proofs).collect();
+        assert!(verify_batch(&setup_params, &batch).is_ok());
+        assert!(verify_batch(&setup_params, &[]).is_ok());
+
+        // The second and fourth ballots are replayed under the other voter's context.
+        let batch = [(&contexts[0], &proofs[0]), (&contexts[0], &proofs[1]), (&contexts[1], &proofs[1]), (&contexts[1], &proofs[0])];
+        let invalid = verify_batch(&setup_params, &batch).unwrap_err().invalid;
+        assert_eq!(invalid.iter().map(|(index, _)| *index).collect::<Vec<usize>>(), vec![1, 3]);
+        assert!(invalid.iter().all(|(_, error)| matches!(error, VerificationError::RangeProofFailed(_))));
     }
 }
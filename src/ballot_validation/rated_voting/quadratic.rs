@@ -0,0 +1,356 @@
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+use curve25519_dalek_ng::{ristretto::CompressedRistretto, scalar::Scalar};
+use bulletproofs::{BulletproofGens, PedersenGens};
+use bulletproofs::r1cs::{ConstraintSystem, LinearCombination, Prover, R1CSError, R1CSProof, Variable, Verifier};
+use crate::ballot_validation::ballot_scheme::{verify_each, BallotScheme};
+use crate::ballot_validation::election_context::ElectionContext;
+use crate::ballot_validation::encoding::{impl_serde_via_bytes, ObjectKind, Reader, SchemeTag, Writer};
+use crate::ballot_validation::errors::{BallotError, BatchVerificationError, DecodingError, VerificationError};
+use super::common::{bit_length, range_constraint};
+
+/// Setup for quadratic voting: signed votes `v_i` with `sum v_i^2 <= credits`.
+pub struct SetupParameters {
+    pc_gens: PedersenGens,
+    bp_gens: BulletproofGens,
+    credits: u64,
+    vote_bound: u64,
+    vote_bits: usize,
+    slack_bits: usize,
+    ballot_size: usize,
+    encoding: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct QuadraticRatedVotingProof {
+    vote_commitments: Vec<CompressedRistretto>,
+    r1cs_proof: R1CSProof,
+}
+
+impl SetupParameters {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encoding.clone()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::QuadraticRatedVoting, ObjectKind::SetupParameters);
+        writer
+            .pc_gens(&self.pc_gens)
+            .u64(self.credits)
+            .u64(self.ballot_size as u64);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SetupParameters, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::QuadraticRatedVoting, ObjectKind::SetupParameters)?;
+        let pc_gens = reader.pc_gens()?;
+        let credits = reader.u64()?;
+        let ballot_size = reader.usize()?;
+        reader.finish()?;
+
+        setup(credits, ballot_size, Some(pc_gens)).map_err(DecodingError::InvalidParameters)
+    }
+}
+
+impl QuadraticRatedVotingProof {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SchemeTag::QuadraticRatedVoting, ObjectKind::Proof);
+        writer
+            .points(&self.vote_commitments)
+            .r1cs_proof(&self.r1cs_proof);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<QuadraticRatedVotingProof, DecodingError> {
+        let mut reader = Reader::new(bytes, SchemeTag::QuadraticRatedVoting, ObjectKind::Proof)?;
+        let vote_commitments = reader.points()?;
+        let r1cs_proof = reader.r1cs_proof()?;
+        reader.finish()?;
+
+        Ok(QuadraticRatedVotingProof {
+            vote_commitments,
+            r1cs_proof
+        })
+    }
+}
+
+impl_serde_via_bytes!(SetupParameters, "quadratic rated voting setup");
+impl_serde_via_bytes!(QuadraticRatedVotingProof, "quadratic rated voting proof");
+
+pub struct QuadraticRatedVoting;
+
+impl BallotScheme for QuadraticRatedVoting {
+    type Params = SetupParameters;
+    type Ballot = [i64];
+    type Proof = QuadraticRatedVotingProof;
+    type Error = BallotError;
+
+    fn generate_vote_with_rng<R: RngCore + CryptoRng>(params: &SetupParameters, context: &ElectionContext, ballot: &[i64], rng: &mut R) -> Result<QuadraticRatedVotingProof, BallotError> {
+        generate_vote_with_rng(params, context, ballot, rng)
+    }
+
+    fn verify_proof(params: &SetupParameters, context: &ElectionContext, proof: &QuadraticRatedVotingProof) -> Result<(), VerificationError> {
+        verify_proof(params, context, proof)
+    }
+
+    fn verify_batch(params: &SetupParameters, ballots: &[(&ElectionContext, &QuadraticRatedVotingProof)]) -> Result<(), BatchVerificationError> {
+        verify_batch(params, ballots)
+    }
+}
+
+pub fn setup(
+    credits: u64,
+    ballot_size: usize,
+    pc_gens: Option<PedersenGens>
+) -> Result<SetupParameters, BallotError> {
+    if ballot_size == 0 {
+        return Err(BallotError::InvalidSetup("ballot_size must be greater than zero".into()));
+    }
+
+    // No single vote can exceed sqrt(credits) in absolute value. Votes are
+    // shifted by vote_bound and range-proven in vote_bits bits, which is enough
+    // to keep the sum of squares from wrapping around the group order.
+    let vote_bound: u64 = credits.isqrt();
+    let vote_bits: usize = bit_length(2 * vote_bound);
+    let slack_bits: usize = bit_length(credits);
+
+    let multipliers: usize = ballot_size
+        .checked_mul(vote_bits + 1)
+        .and_then(|n| n.checked_add(slack_bits))
+        .ok_or_else(|| BallotError::InvalidSetup("ballot_size is too large".into()))?;
+    let pc_gens: PedersenGens = pc_gens.unwrap_or_default();
+    let bp_gens: BulletproofGens = BulletproofGens::new(multipliers.next_power_of_two(), 1);
+
+    let mut setup_params = SetupParameters {
+        pc_gens,
+        bp_gens,
+        credits,
+        vote_bound,
+        vote_bits,
+        slack_bits,
+        ballot_size,
+        encoding: Vec::new(),
+    };
+    setup_params.encoding = setup_params.encode();
+
+    Ok(setup_params)
+}
+
+pub fn generate_vote(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[i64],
+) -> Result<QuadraticRatedVotingProof, BallotError> {
+    generate_vote_with_rng(setup_params, context, ballot, &mut OsRng)
+}
+
+pub fn generate_vote_with_rng<R: RngCore + CryptoRng>(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    ballot: &[i64],
+    rng: &mut R,
+) -> Result<QuadraticRatedVotingProof, BallotError> {
+    if ballot.len() != setup_params.ballot_size {
+        return Err(BallotError::LengthMismatch { expected: setup_params.ballot_size, actual: ballot.len() });
+    }
+
+    if let Some(index) = ballot.iter().position(|vote| vote.unsigned_abs() > setup_params.vote_bound) {
+        return Err(BallotError::VoteOutOfRange { index });
+    }
+
+    let spent: u64 = ballot
+        .iter()
+        .try_fold(0u64, |acc, vote| acc.checked_add(vote.unsigned_abs() * vote.unsigned_abs()))
+        .ok_or(BallotError::Overflow)?;
+    let slack: u64 = setup_params
+        .credits
+        .checked_sub(spent)
+        .ok_or(BallotError::BudgetExceeded { sum: spent, max: setup_params.credits })?;
+
+    let mut transcript = context.scheme_transcript(b"quadratic rated voting", &setup_params.encoding);
+    let mut prover = Prover::new(&setup_params.pc_gens, &mut transcript);
+
+    let (vote_commitments, vote_variables): (Vec<CompressedRistretto>, Vec<Variable>) = ballot
+        .iter()
+        .map(|&vote| prover.commit(signed_scalar(vote), Scalar::random(rng)))
+        .unzip();
+
+    budget_constraints(&mut prover, setup_params, &vote_variables, Some((ballot, slack)))
+        .map_err(BallotError::ConstraintProofFailed)?;
+
+    let r1cs_proof = prover
+        .prove(&setup_params.bp_gens)
+        .map_err(BallotError::ConstraintProofFailed)?;
+
+    Ok(QuadraticRatedVotingProof {
+        vote_commitments,
+        r1cs_proof
+    })
+}
+
+pub fn verify_proof(
+    setup_params: &SetupParameters,
+    context: &ElectionContext,
+    validity_proof: &QuadraticRatedVotingProof,
+) -> Result<(), VerificationError> {
+    if validity_proof.vote_commitments.len() != setup_params.ballot_size {
+        return Err(VerificationError::LengthMismatch { expected: setup_params.ballot_size, actual: validity_proof.vote_commitments.len() });
+    }
+
+    let mut transcript = context.scheme_transcript(b"quadratic rated voting", &setup_params.encoding);
+    let mut verifier = Verifier::new(&mut transcript);
+
+    let vote_variables: Vec<Variable> = validity_proof
+        .vote_commitments
+        .iter()
+        .map(|commitment| verifier.commit(*commitment))
+        .collect();
+
+    budget_constraints(&mut verifier, setup_params, &vote_variables, None)
+        .map_err(VerificationError::ConstraintProofFailed)?;
+
+    verifier
+        .verify(&validity_proof.r1cs_proof, &setup_params.pc_gens, &setup_params.bp_gens)
+        .map_err(VerificationError::ConstraintProofFailed)
+}
+
+/// A quadratic ballot is a single R1CS proof, so the ballots are verified
+/// individually.
+pub fn verify_batch(
+    setup_params: &SetupParameters,
+    ballots: &[(&ElectionContext, &QuadraticRatedVotingProof)],
+) -> Result<(), BatchVerificationError> {
+    verify_each(ballots, |context, proof| verify_proof(setup_params, context, proof))
+}
+
+/// Constrains every shifted vote `v_i + vote_bound` to `[0, 2^vote_bits)` and
+/// `credits - sum v_i^2` to be non-negative. The witness is the ballot and its
+/// unspent credits.
+fn budget_constraints<CS: ConstraintSystem>(
+    cs: &mut CS,
+    setup_params: &SetupParameters,
+    votes: &[Variable],
+    witness: Option<(&[i64], u64)>,
+) -> Result<(), R1CSError> {
+    let mut spent = LinearCombination::default();
+
+    for (index, vote) in votes.iter().enumerate() {
+        let shifted_vote = witness.map(|(ballot, _)| (ballot[index] as i128 + setup_params.vote_bound as i128) as u64);
+        range_constraint(cs, *vote + Scalar::from(setup_params.vote_bound), shifted_vote, setup_params.vote_bits)?;
+
+        let (_, _, square) = cs.multiply((*vote).into(), (*vote).into());
+        spent = spent + square;
+    }
+
+    let slack = witness.map(|(_, slack)| slack);
+    range_constraint(cs, LinearCombination::from(setup_params.credits) - spent, slack, setup_params.slack_bits)
+}
+
+fn signed_scalar(value: i64) -> Scalar {
+    if value < 0 {
+        -Scalar::from(value.unsigned_abs())
+    } else {
+        Scalar::from(value as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ballot_validation::election_context::test_context;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_valid_signed_ballot() {
+        let setup_params = setup(30, 4, None).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), &[-3, 4, 0, -2]).expect("Should generate proof");
+        assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok(), "Ballot spending 29 of 30 credits should verify");
+
+        let proof = generate_vote(&setup_params, &test_context(), &[5, -2, 1, 0]).unwrap();
+        assert!(verify_proof(&setup_params, &test_context(), &proof).is_ok(), "Ballot spending all credits should verify");
+    }
+
+    #[test]
+    fn test_overspending_fails() {
+        let setup_params = setup(30, 4, None).unwrap();
+        assert_eq!(generate_vote(&setup_params, &test_context(), &[4, 4, 0, 0]).err(), Some(BallotError::BudgetExceeded { sum: 32, max: 30 }));
+        assert_eq!(generate_vote(&setup_params, &test_context(), &[0, -6, 0, 0]).err(), Some(BallotError::VoteOutOfRange { index: 1 }));
+    }
+
+    #[test]
+    fn test_overspending_proof_fails() {
+        let setup_params = setup(30, 2, None).unwrap();
+        let ballot = [5, -5];
+
+        // Both votes are within the bound, but they spend 50 of 30 credits, so
+        // the claimed slack of zero breaks the credit constraint.
+        let mut transcript = test_context().scheme_transcript(b"quadratic rated voting", &setup_params.encoding);
+        let mut prover = Prover::new(&setup_params.pc_gens, &mut transcript);
+        let (vote_commitments, vote_variables): (Vec<CompressedRistretto>, Vec<Variable>) = ballot
+            .iter()
+            .map(|&vote| prover.commit(signed_scalar(vote), Scalar::random(&mut thread_rng())))
+            .unzip();
+        budget_constraints(&mut prover, &setup_params, &vote_variables, Some((&ballot, 0))).unwrap();
+        let r1cs_proof = prover.prove(&setup_params.bp_gens).unwrap();
+
+        let proof = QuadraticRatedVotingProof { vote_commitments, r1cs_proof };
+        assert!(matches!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::ConstraintProofFailed(_))));
+    }
+
+    #[test]
+    fn test_tampered_commitment_fails() {
+        let setup_params = setup(30, 2, None).unwrap();
+        let mut proof = generate_vote(&setup_params, &test_context(), &[3, 4]).unwrap();
+
+        proof.vote_commitments.swap(0, 1);
+        assert!(matches!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::ConstraintProofFailed(_))));
+
+        proof.vote_commitments.pop();
+        assert_eq!(verify_proof(&setup_params, &test_context(), &proof), Err(VerificationError::LengthMismatch { expected: 2, actual: 1 }));
+    }
+
+    #[test]
+    fn test_malformed_setup_is_rejected() {
+        let mut bytes = setup(1_000_000, 3, None).unwrap().to_bytes();
+        let len = bytes.len();
+        bytes[len - 8..].copy_from_slice(&0u64.to_le_bytes());
+        assert!(matches!(SetupParameters::from_bytes(&bytes), Err(DecodingError::InvalidParameters(BallotError::InvalidSetup(_)))));
+    }
+
+    #[test]
+    fn test_proof_is_bound_to_context_and_setup() {
+        let setup_params = setup(30, 3, None).unwrap();
+        let proof = generate_vote(&setup_params, &test_context(), &[-3, 4, 0]).unwrap();
+
+        for context in [
+            ElectionContext::new(b"test election", b"other contest", b"test voter"),
+            ElectionContext::new(b"test election", b"test contest", b"other voter"),
+        ] {
+            assert!(matches!(verify_proof(&setup_params, &context, &proof), Err(VerificationError::ConstraintProofFailed(_))));
+        }
+
+        let other = setup(31, 3, None).unwrap();
+        assert!(matches!(verify_proof(&other, &test_context(), &proof), Err(VerificationError::ConstraintProofFailed(_))));
+    }
+
+    #[test]
+    fn test_batch_verification() {
+        let setup_params = setup(30, 3, None).unwrap();
+        let contexts: Vec<ElectionContext> = (0..2)
+            .map(|voter| ElectionContext::new(b"test election", b"test contest", &[voter]))
+            .collect();
+        let proofs: Vec<QuadraticRatedVotingProof> = contexts
+            .iter()
+            .map(|context| generate_vote(&setup_params, context, &[-3, 4, 0]).unwrap())
+            .collect();
+
+        let batch: Vec<(&ElectionContext, &QuadraticRatedVotingProof)> = contexts.iter().zip(&proofs).collect();
+        assert!(verify_batch(&setup_params, &batch).is_ok());
+        assert!(verify_batch(&setup_params, &[]).is_ok());
+
+        // The second and fourth ballots are replayed under the other voter's context.
+        let batch = [(&contexts[0], &proofs[0]), (&contexts[0], &proofs[1]), (&contexts[1], &proofs[1]), (&contexts[1], &proofs[0])];
+        let invalid = verify_batch(&setup_params, &batch).unwrap_err().invalid;
+        assert_eq!(invalid.iter().map(|(index, _)| *index).collect::<Vec<usize>>(), vec![1, 3]);
+        assert!(invalid.iter().all(|(_, error)| matches!(error, VerificationError::ConstraintProofFailed(_))));
+    }
+}
@@ -0,0 +1,158 @@
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+use curve25519_dalek_ng::{ristretto::{CompressedRistretto, RistrettoPoint}, scalar::Scalar};
+use curve25519_dalek_ng::traits::{IsIdentity, VartimeMultiscalarMul};
+use crate::ballot_validation::encoding::{Reader, Writer};
+use crate::ballot_validation::errors::DecodingError;
+
+/// Schnorr proof of knowledge of the discrete log of a point with respect to
+/// a generator. Proving that a Pedersen commitment opens to zero is the case
+/// where the generator is the blinding generator.
+#[derive(Clone)]
+pub(crate) struct DiscreteLogProof {
+    pub(crate) nonce_commitment: CompressedRistretto,
+    pub(crate) response: Scalar,
+}
+
+impl DiscreteLogProof {
+    /// Proves knowledge of `secret` such that `point = secret * generator`.
+    pub(crate) fn prove<R: RngCore + CryptoRng>(
+        transcript: &mut Transcript,
+        generator: &RistrettoPoint,
+        point: &RistrettoPoint,
+        secret: &Scalar,
+        rng: &mut R,
+    ) -> DiscreteLogProof {
+        let nonce = Scalar::random(rng);
+        let nonce_commitment = (nonce * generator).compress();
+        let challenge = discrete_log_challenge(transcript, point, &nonce_commitment);
+
+        DiscreteLogProof {
+            nonce_commitment,
+            response: nonce + challenge * secret,
+        }
+    }
+
+    pub(crate) fn verify(
+        &self,
+        transcript: &mut Transcript,
+        generator: &RistrettoPoint,
+        point: &RistrettoPoint,
+    ) -> bool {
+        let challenge = discrete_log_challenge(transcript, point, &self.nonce_commitment);
+        match self.nonce_commitment.decompress() {
+            Some(nonce_commitment) => self.response * generator == nonce_commitment + challenge * point,
+            None => false,
+        }
+    }
+
+    /// Like `verify`, but adds the check to `batch` instead of running it.
+    /// Returns `false` if the proof is malformed.
+    pub(crate) fn verify_deferred<R: RngCore + CryptoRng>(
+        &self,
+        transcript: &mut Transcript,
+        generator: &RistrettoPoint,
+        point: &RistrettoPoint,
+        batch: &mut BatchCheck,
+        rng: &mut R,
+    ) -> bool {
+        let challenge = discrete_log_challenge(transcript, point, &self.nonce_commitment);
+        match self.nonce_commitment.decompress() {
+            Some(nonce_commitment) => {
+                batch.add(rng, &[(self.response, *generator), (-Scalar::one(), nonce_commitment), (-challenge, *point)]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn write(&self, writer: &mut Writer) {
+        writer.point(&self.nonce_commitment).scalar(&self.response);
+    }
+
+    pub(crate) fn read(reader: &mut Reader) -> Result<DiscreteLogProof, DecodingError> {
+        Ok(DiscreteLogProof {
+            nonce_commitment: reader.point()?,
+            response: reader.scalar()?,
+        })
+    }
+}
+
+fn discrete_log_challenge(
+    transcript: &mut Transcript,
+    point: &RistrettoPoint,
+    nonce_commitment: &CompressedRistretto,
+) -> Scalar {
+    transcript.append_message(b"dlog point", point.compress().as_bytes());
+    transcript.append_message(b"dlog nonce", nonce_commitment.as_bytes());
+    let mut challenge = [0u8; 64];
+    transcript.challenge_bytes(b"dlog challenge", &mut challenge);
+    Scalar::from_bytes_mod_order_wide(&challenge)
+}
+
+/// Equations of the form `sum(scalar * point) == 0`, each weighted by a fresh
+/// random scalar so that a whole batch is checked with one multiscalar
+/// multiplication.
+#[derive(Default)]
+pub(crate) struct BatchCheck {
+    scalars: Vec<Scalar>,
+    points: Vec<RistrettoPoint>,
+}
+
+impl BatchCheck {
+    pub(crate) fn add<R: RngCore + CryptoRng>(&mut self, rng: &mut R, terms: &[(Scalar, RistrettoPoint)]) {
+        let weight = Scalar::random(rng);
+        for (scalar, point) in terms {
+            self.scalars.push(weight * scalar);
+            self.points.push(*point);
+        }
+    }
+
+    pub(crate) fn verify(&self) -> bool {
+        RistrettoPoint::vartime_multiscalar_mul(&self.scalars, &self.points).is_identity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bulletproofs::PedersenGens;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_discrete_log_proof() {
+        let pc_gens = PedersenGens::default();
+        let blinding = Scalar::random(&mut thread_rng());
+        let commitment = pc_gens.commit(Scalar::zero(), blinding);
+
+        let proof = DiscreteLogProof::prove(&mut Transcript::new(b"test"), &pc_gens.B_blinding, &commitment, &blinding, &mut thread_rng());
+        assert!(proof.verify(&mut Transcript::new(b"test"), &pc_gens.B_blinding, &commitment));
+        assert!(!proof.verify(&mut Transcript::new(b"other"), &pc_gens.B_blinding, &commitment));
+
+        let nonzero = pc_gens.commit(Scalar::one(), blinding);
+        let forged = DiscreteLogProof::prove(&mut Transcript::new(b"test"), &pc_gens.B_blinding, &nonzero, &blinding, &mut thread_rng());
+        assert!(!forged.verify(&mut Transcript::new(b"test"), &pc_gens.B_blinding, &nonzero));
+    }
+
+    #[test]
+    fn test_batched_discrete_log_proofs() {
+        let pc_gens = PedersenGens::default();
+        let blindings: Vec<Scalar> = (0..4).map(|_| Scalar::random(&mut thread_rng())).collect();
+        let commitments: Vec<RistrettoPoint> = blindings.iter().map(|&blinding| pc_gens.commit(Scalar::zero(), blinding)).collect();
+        let proofs: Vec<DiscreteLogProof> = commitments
+            .iter()
+            .zip(&blindings)
+            .map(|(commitment, blinding)| DiscreteLogProof::prove(&mut Transcript::new(b"test"), &pc_gens.B_blinding, commitment, blinding, &mut thread_rng()))
+            .collect();
+
+        let mut batch = BatchCheck::default();
+        for (proof, commitment) in proofs.iter().zip(&commitments) {
+            assert!(proof.verify_deferred(&mut Transcript::new(b"test"), &pc_gens.B_blinding, commitment, &mut batch, &mut thread_rng()));
+        }
+        assert!(batch.verify());
+
+        let nonzero = pc_gens.commit(Scalar::one(), blindings[0]);
+        proofs[0].verify_deferred(&mut Transcript::new(b"test"), &pc_gens.B_blinding, &nonzero, &mut batch, &mut thread_rng());
+        assert!(!batch.verify(), "One bad equation should fail the whole batch");
+    }
+}
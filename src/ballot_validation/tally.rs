@@ -0,0 +1,243 @@
+use std::marker::PhantomData;
+use rand::{CryptoRng, RngCore};
+use curve25519_dalek_ng::{ristretto::{CompressedRistretto, RistrettoPoint}, scalar::Scalar, traits::Identity};
+use bulletproofs::PedersenGens;
+use crate::ballot_validation::ballot_scheme::BallotScheme;
+use crate::ballot_validation::election_context::ElectionContext;
+use crate::ballot_validation::errors::{BallotError, VerificationError};
+
+/// Opening of a per-candidate commitment: the vote and its blinding, or the
+/// total and summed blinding once aggregated over ballots. The value is the
+/// vote itself; any offset a scheme adds before committing is applied by the
+/// tally.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CandidateOpening {
+    pub value: i128,
+    pub blinding: Scalar,
+}
+
+/// Rated schemes whose ballots carry one Pedersen commitment per candidate.
+pub trait TallyScheme: BallotScheme {
+    /// Generators, number of candidates and the offset added to every vote
+    /// before it is committed.
+    fn tally_setup(params: &Self::Params) -> (PedersenGens, usize, i64);
+
+    /// Wire encoding of the parameters, bound into every transcript.
+    fn setup_bytes(params: &Self::Params) -> &[u8];
+
+    /// Smallest and largest vote a ballot may give a single candidate.
+    fn vote_bounds(params: &Self::Params) -> (i128, i128);
+
+    fn vote_commitments(proof: &Self::Proof) -> &[CompressedRistretto];
+
+    /// Generates the ballot proof together with the openings the voter hands
+    /// to the election authority.
+    fn generate_vote_with_openings<R: RngCore + CryptoRng>(
+        params: &Self::Params,
+        context: &ElectionContext,
+        ballot: &Self::Ballot,
+        rng: &mut R,
+    ) -> Result<(Self::Proof, Vec<CandidateOpening>), Self::Error>;
+}
+
+/// Homomorphic per-candidate sum of the commitments of accepted ballots.
+pub struct Tally<S: TallyScheme> {
+    pc_gens: PedersenGens,
+    offset: i64,
+    sums: Vec<RistrettoPoint>,
+    ballot_count: u64,
+    scheme: PhantomData<S>,
+}
+
+impl<S: TallyScheme> Tally<S> {
+    pub fn new(params: &S::Params) -> Tally<S> {
+        let (pc_gens, ballot_size, offset) = S::tally_setup(params);
+        Tally {
+            pc_gens,
+            offset,
+            sums: vec![RistrettoPoint::identity(); ballot_size],
+            ballot_count: 0,
+            scheme: PhantomData,
+        }
+    }
+
+    /// Verifies the ballot and adds its commitments to the sums.
+    pub fn add(&mut self, params: &S::Params, context: &ElectionContext, proof: &S::Proof) -> Result<(), VerificationError> {
+        S::verify_proof(params, context, proof)?;
+        self.add_verified(proof)
+    }
+
+    /// Adds a ballot without checking its proof, for ballots that have
+    /// already been verified, e.g. with `verify_batch`. An unverified ballot
+    /// added here silently corrupts the sums.
+    pub fn add_verified(&mut self, proof: &S::Proof) -> Result<(), VerificationError> {
+        let commitments: &[CompressedRistretto] = S::vote_commitments(proof);
+        if commitments.len() != self.sums.len() {
+            return Err(VerificationError::LengthMismatch { expected: self.sums.len(), actual: commitments.len() });
+        }
+
+        // Decompress everything first so a malformed ballot leaves the sums untouched.
+        let points: Vec<RistrettoPoint> = commitments
+            .iter()
+            .map(|c| c.decompress().ok_or(VerificationError::DecompressionFailed))
+            .collect::<Result<_, _>>()?;
+
+        for (sum, point) in self.sums.iter_mut().zip(points) {
+            *sum += point;
+        }
+        self.ballot_count += 1;
+
+        Ok(())
+    }
+
+    pub fn ballot_count(&self) -> u64 {
+        self.ballot_count
+    }
+
+    pub fn sums(&self) -> &[RistrettoPoint] {
+        &self.sums
+    }
+
+    /// Checks the authority's aggregated openings against the homomorphic
+    /// sums. Each sum commits to the total plus the scheme offset once per
+    /// ballot, which is added back before comparing.
+    pub fn verify_totals(&self, openings: &[CandidateOpening]) -> Result<(), VerificationError> {
+        if openings.len() != self.sums.len() {
+            return Err(VerificationError::LengthMismatch { expected: self.sums.len(), actual: openings.len() });
+        }
+
+        let shift: i128 = self.ballot_count as i128 * self.offset as i128;
+        for (candidate, (opening, sum)) in openings.iter().zip(&self.sums).enumerate() {
+            let committed: i128 = opening.value.checked_add(shift).ok_or(VerificationError::TallyMismatch { candidate })?;
+            if self.pc_gens.commit(scalar_from_i128(committed), opening.blinding) != *sum {
+                return Err(VerificationError::TallyMismatch { candidate });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Sums the openings of every ballot per candidate, as done by the election
+/// authority before publishing the totals.
+pub fn aggregate_openings(ballot_openings: &[Vec<CandidateOpening>], ballot_size: usize) -> Result<Vec<CandidateOpening>, BallotError> {
+    let mut totals: Vec<CandidateOpening> = vec![CandidateOpening { value: 0, blinding: Scalar::zero() }; ballot_size];
+
+    for openings in ballot_openings {
+        if openings.len() != ballot_size {
+            return Err(BallotError::LengthMismatch { expected: ballot_size, actual: openings.len() });
+        }
+        for (total, opening) in totals.iter_mut().zip(openings) {
+            total.value = total.value.checked_add(opening.value).ok_or(BallotError::Overflow)?;
+            total.blinding += opening.blinding;
+        }
+    }
+
+    Ok(totals)
+}
+
+pub(crate) fn scalar_from_i128(value: i128) -> Scalar {
+    if value < 0 {
+        -Scalar::from(value.unsigned_abs())
+    } else {
+        Scalar::from(value as u128)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+    use crate::ballot_validation::election_context::test_context;
+    use crate::ballot_validation::rated_voting::exact_budget::{self, ExactBudgetRatedVoting};
+    use crate::ballot_validation::rated_voting::max_budget::{self, MaxBudgetRatedVoting};
+    use crate::ballot_validation::rated_voting::no_budget::{self, NoBudgetRatedVoting};
+
+    fn cast<S: TallyScheme>(params: &S::Params, ballots: &[&S::Ballot]) -> (Tally<S>, Vec<CandidateOpening>)
+    where
+        S::Error: std::fmt::Debug,
+    {
+        let context = test_context();
+        let mut tally = Tally::<S>::new(params);
+        let mut ballot_openings = Vec::new();
+
+        for ballot in ballots {
+            let (proof, openings) = S::generate_vote_with_openings(params, &context, ballot, &mut thread_rng()).unwrap();
+            tally.add(params, &context, &proof).unwrap();
+            ballot_openings.push(openings);
+        }
+
+        let size = tally.sums().len();
+        (tally, aggregate_openings(&ballot_openings, size).unwrap())
+    }
+
+    #[test]
+    fn test_max_budget_totals() {
+        let params = max_budget::setup(10, None, None, 4, None).unwrap();
+        let (tally, totals) = cast::<MaxBudgetRatedVoting>(&params, &[&[4, 6, 0, 0], &[1, 2, 3, 4], &[0, 0, 10, 0]]);
+
+        assert_eq!(tally.ballot_count(), 3);
+        assert_eq!(totals.iter().map(|t| t.value).collect::<Vec<_>>(), vec![5, 8, 13, 4]);
+        assert!(tally.verify_totals(&totals).is_ok());
+    }
+
+    #[test]
+    fn test_no_budget_totals_are_unshifted() {
+        let params = no_budget::setup((-5, 5), 4, None).unwrap();
+        let (tally, totals) = cast::<NoBudgetRatedVoting>(&params, &[&[-5, 0, 5, 2], &[-3, -2, 4, 0], &[1, -5, 5, -1]]);
+
+        assert_eq!(totals.iter().map(|t| t.value).collect::<Vec<_>>(), vec![-7, -7, 14, 1]);
+        assert!(tally.verify_totals(&totals).is_ok());
+    }
+
+    #[test]
+    fn test_wrong_total_is_rejected() {
+        let params = exact_budget::setup(6, None, 2, None).unwrap();
+        let (tally, mut totals) = cast::<ExactBudgetRatedVoting>(&params, &[&[6, 0], &[2, 4]]);
+        assert!(tally.verify_totals(&totals).is_ok());
+
+        // Moving a vote between candidates keeps the overall sum but not the per-candidate sums.
+        totals[0].value -= 1;
+        totals[1].value += 1;
+        assert_eq!(tally.verify_totals(&totals), Err(VerificationError::TallyMismatch { candidate: 0 }));
+
+        totals[0].value += 1;
+        totals[1].blinding += Scalar::one();
+        assert_eq!(tally.verify_totals(&totals), Err(VerificationError::TallyMismatch { candidate: 1 }));
+    }
+
+    #[test]
+    fn test_invalid_ballot_is_not_added() {
+        let params = max_budget::setup(10, None, None, 2, None).unwrap();
+        let (mut tally, totals) = cast::<MaxBudgetRatedVoting>(&params, &[&[4, 6]]);
+
+        let (proof, _) = MaxBudgetRatedVoting::generate_vote_with_openings(&params, &test_context(), &[10, 0], &mut thread_rng()).unwrap();
+        let other = ElectionContext::new(b"test election", b"test contest", b"other voter");
+        assert!(tally.add(&params, &other, &proof).is_err());
+
+        assert_eq!(tally.ballot_count(), 1);
+        assert!(tally.verify_totals(&totals).is_ok());
+    }
+
+    #[test]
+    fn test_empty_tally() {
+        let params = no_budget::setup((-5, 5), 2, None).unwrap();
+        let tally = Tally::<NoBudgetRatedVoting>::new(&params);
+        let totals = aggregate_openings(&[], 2).unwrap();
+
+        assert!(tally.verify_totals(&totals).is_ok());
+        assert_eq!(tally.verify_totals(&totals[..1]), Err(VerificationError::LengthMismatch { expected: 2, actual: 1 }));
+    }
+
+    #[test]
+    fn test_mismatched_openings_are_rejected() {
+        let opening = CandidateOpening { value: 1, blinding: Scalar::one() };
+        assert_eq!(
+            aggregate_openings(&[vec![opening; 2], vec![opening; 3]], 2),
+            Err(BallotError::LengthMismatch { expected: 2, actual: 3 })
+        );
+
+        let overflowing = CandidateOpening { value: i128::MAX, blinding: Scalar::zero() };
+        assert_eq!(aggregate_openings(&[vec![overflowing], vec![opening]], 1), Err(BallotError::Overflow));
+    }
+}
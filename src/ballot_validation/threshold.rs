@@ -0,0 +1,501 @@
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+use zeroize::Zeroize;
+use curve25519_dalek_ng::{ristretto::{CompressedRistretto, RistrettoPoint}, scalar::Scalar};
+use bulletproofs::PedersenGens;
+use crate::ballot_validation::election_context::ElectionContext;
+use crate::ballot_validation::elgamal::{Ciphertext, PublicKey};
+use crate::ballot_validation::errors::ThresholdError;
+use crate::ballot_validation::sigma::DiscreteLogProof;
+
+/// A `threshold`-of-`trustees` sharing of the election decryption key.
+/// Trustees are numbered from 1, which is also where their shares are
+/// evaluated.
+pub struct ThresholdParameters {
+    pc_gens: PedersenGens,
+    threshold: usize,
+    trustees: usize,
+}
+
+/// The broadcast part of a trustee's Feldman dealing: commitments to the
+/// coefficients of its secret polynomial and a proof of knowledge of the
+/// constant term.
+#[derive(Clone)]
+pub struct Dealing {
+    dealer: usize,
+    commitments: Vec<RistrettoPoint>,
+    proof: DiscreteLogProof,
+}
+
+/// A trustee's secret polynomial. The shares it yields are sent privately to
+/// the other trustees. The coefficients are zeroed on drop.
+pub struct Dealer {
+    coefficients: Vec<Scalar>,
+    dealing: Dealing,
+}
+
+/// The joint public key and the public verification key of every trustee.
+pub struct ElectionKey {
+    public_key: PublicKey,
+    verification_keys: Vec<RistrettoPoint>,
+}
+
+/// A trustee's share of the decryption key, zeroed on drop.
+pub struct KeyShare {
+    trustee: usize,
+    secret: Scalar,
+}
+
+/// One trustee's share of the decryption of several ciphertexts, with a
+/// Chaum-Pedersen proof for each.
+#[derive(Clone)]
+pub struct PartialDecryption {
+    trustee: usize,
+    shares: Vec<RistrettoPoint>,
+    proofs: Vec<DecryptionProof>,
+}
+
+/// Chaum-Pedersen proof that `share = x * c1` for the `x` behind the
+/// trustee's verification key `x * B`.
+#[derive(Clone)]
+struct DecryptionProof {
+    nonce_commitments: [CompressedRistretto; 2],
+    response: Scalar,
+}
+
+pub fn setup(threshold: usize, trustees: usize, pc_gens: Option<PedersenGens>) -> Result<ThresholdParameters, ThresholdError> {
+    if threshold == 0 || threshold > trustees {
+        return Err(ThresholdError::InvalidParameters("threshold must be between 1 and the number of trustees".into()));
+    }
+
+    Ok(ThresholdParameters {
+        pc_gens: pc_gens.unwrap_or_default(),
+        threshold,
+        trustees,
+    })
+}
+
+impl ThresholdParameters {
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn trustees(&self) -> usize {
+        self.trustees
+    }
+
+    fn check_trustee(&self, trustee: usize) -> Result<(), ThresholdError> {
+        if trustee == 0 || trustee > self.trustees {
+            return Err(ThresholdError::UnknownTrustee { trustee });
+        }
+        Ok(())
+    }
+}
+
+impl Dealer {
+    pub fn new<R: RngCore + CryptoRng>(
+        params: &ThresholdParameters,
+        context: &ElectionContext,
+        dealer: usize,
+        rng: &mut R,
+    ) -> Result<Dealer, ThresholdError> {
+        params.check_trustee(dealer)?;
+
+        let coefficients: Vec<Scalar> = (0..params.threshold).map(|_| Scalar::random(rng)).collect();
+        let commitments: Vec<RistrettoPoint> = coefficients.iter().map(|a| a * params.pc_gens.B).collect();
+        let proof = DiscreteLogProof::prove(
+            &mut dealing_transcript(context, dealer, &commitments),
+            &params.pc_gens.B,
+            &commitments[0],
+            &coefficients[0],
+            rng,
+        );
+
+        Ok(Dealer {
+            coefficients,
+            dealing: Dealing { dealer, commitments, proof },
+        })
+    }
+
+    pub fn dealing(&self) -> &Dealing {
+        &self.dealing
+    }
+
+    /// The share for `trustee`, to be sent to it over a private channel.
+    pub fn share_for(&self, trustee: usize) -> Scalar {
+        // Horner evaluation of the polynomial at `trustee`.
+        let x = Scalar::from(trustee as u64);
+        self.coefficients.iter().rev().fold(Scalar::zero(), |acc, a| acc * x + a)
+    }
+}
+
+impl Drop for Dealer {
+    fn drop(&mut self) {
+        self.coefficients.zeroize();
+    }
+}
+
+impl Dealing {
+    pub fn dealer(&self) -> usize {
+        self.dealer
+    }
+
+    /// Checks the shape of the dealing and the proof of knowledge of its
+    /// constant term.
+    pub fn verify(&self, params: &ThresholdParameters, context: &ElectionContext) -> Result<(), ThresholdError> {
+        params.check_trustee(self.dealer)?;
+        if self.commitments.len() != params.threshold
+            || !self.proof.verify(&mut dealing_transcript(context, self.dealer, &self.commitments), &params.pc_gens.B, &self.commitments[0])
+        {
+            return Err(ThresholdError::InvalidDealing { dealer: self.dealer });
+        }
+        Ok(())
+    }
+
+    /// Checks a privately received share against the public commitments.
+    pub fn verify_share(&self, params: &ThresholdParameters, trustee: usize, share: &Scalar) -> Result<(), ThresholdError> {
+        params.check_trustee(trustee)?;
+        if share * params.pc_gens.B != self.evaluate_commitments(trustee) {
+            return Err(ThresholdError::InvalidShare { dealer: self.dealer });
+        }
+        Ok(())
+    }
+
+    /// `f(trustee) * B`, computed from the coefficient commitments alone.
+    fn evaluate_commitments(&self, trustee: usize) -> RistrettoPoint {
+        let x = Scalar::from(trustee as u64);
+        self.commitments.iter().rev().fold(RistrettoPoint::default(), |acc, a| acc * x + a)
+    }
+}
+
+impl ElectionKey {
+    /// Combines the dealings of all trustees. Every dealing must be present
+    /// and valid.
+    pub fn from_dealings(params: &ThresholdParameters, context: &ElectionContext, dealings: &[Dealing]) -> Result<ElectionKey, ThresholdError> {
+        check_dealings(params, context, dealings)?;
+
+        let public_key = PublicKey::from_point(dealings.iter().map(|d| d.commitments[0]).sum());
+        let verification_keys: Vec<RistrettoPoint> = (1..=params.trustees)
+            .map(|trustee| dealings.iter().map(|d| d.evaluate_commitments(trustee)).sum())
+            .collect();
+
+        Ok(ElectionKey { public_key, verification_keys })
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    pub fn verification_key(&self, trustee: usize) -> Option<&RistrettoPoint> {
+        self.verification_keys.get(trustee.checked_sub(1)?)
+    }
+}
+
+impl KeyShare {
+    /// Verifies the shares `trustee` received, where `shares[i]` came from
+    /// the dealer of `dealings[i]`, and sums them into its key share.
+    pub fn from_shares(
+        params: &ThresholdParameters,
+        context: &ElectionContext,
+        trustee: usize,
+        dealings: &[Dealing],
+        shares: &[Scalar],
+    ) -> Result<KeyShare, ThresholdError> {
+        check_dealings(params, context, dealings)?;
+        if shares.len() != dealings.len() {
+            return Err(ThresholdError::LengthMismatch { expected: dealings.len(), actual: shares.len() });
+        }
+
+        for (dealing, share) in dealings.iter().zip(shares) {
+            dealing.verify_share(params, trustee, share)?;
+        }
+
+        Ok(KeyShare { trustee, secret: shares.iter().sum() })
+    }
+
+    pub fn trustee(&self) -> usize {
+        self.trustee
+    }
+
+    pub fn partial_decrypt<R: RngCore + CryptoRng>(
+        &self,
+        params: &ThresholdParameters,
+        context: &ElectionContext,
+        ciphertexts: &[Ciphertext],
+        rng: &mut R,
+    ) -> PartialDecryption {
+        let verification_key: RistrettoPoint = self.secret * params.pc_gens.B;
+        let mut transcript = decryption_transcript(context, self.trustee);
+
+        let (shares, proofs): (Vec<RistrettoPoint>, Vec<DecryptionProof>) = ciphertexts
+            .iter()
+            .map(|ciphertext| {
+                let share: RistrettoPoint = self.secret * ciphertext.c1();
+                let nonce = Scalar::random(rng);
+                let nonce_commitments = [nonce * params.pc_gens.B, nonce * ciphertext.c1()].map(|point| point.compress());
+                let challenge = decryption_challenge(&mut transcript, &verification_key, ciphertext.c1(), &share, &nonce_commitments);
+
+                (share, DecryptionProof { nonce_commitments, response: nonce + challenge * self.secret })
+            })
+            .unzip();
+
+        PartialDecryption { trustee: self.trustee, shares, proofs }
+    }
+}
+
+impl Drop for KeyShare {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+impl PartialDecryption {
+    pub fn trustee(&self) -> usize {
+        self.trustee
+    }
+
+    pub fn verify(
+        &self,
+        params: &ThresholdParameters,
+        context: &ElectionContext,
+        election_key: &ElectionKey,
+        ciphertexts: &[Ciphertext],
+    ) -> Result<(), ThresholdError> {
+        let verification_key: &RistrettoPoint = election_key
+            .verification_key(self.trustee)
+            .ok_or(ThresholdError::UnknownTrustee { trustee: self.trustee })?;
+        for len in [self.shares.len(), self.proofs.len()] {
+            if len != ciphertexts.len() {
+                return Err(ThresholdError::LengthMismatch { expected: ciphertexts.len(), actual: len });
+            }
+        }
+
+        let mut transcript = decryption_transcript(context, self.trustee);
+        for ((ciphertext, share), proof) in ciphertexts.iter().zip(&self.shares).zip(&self.proofs) {
+            let challenge = decryption_challenge(&mut transcript, verification_key, ciphertext.c1(), share, &proof.nonce_commitments);
+            let valid = match (proof.nonce_commitments[0].decompress(), proof.nonce_commitments[1].decompress()) {
+                (Some(on_base), Some(on_c1)) => {
+                    proof.response * params.pc_gens.B == on_base + challenge * verification_key
+                        && proof.response * ciphertext.c1() == on_c1 + challenge * share
+                }
+                _ => false,
+            };
+            if !valid {
+                return Err(ThresholdError::InvalidPartialDecryption { trustee: self.trustee });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Verifies the partial decryptions and combines the first `threshold` of
+/// them by Lagrange interpolation at zero. Returns `v * B` for the value `v`
+/// of each ciphertext; recovering `v` is a bounded discrete log.
+pub fn combine_partial_decryptions(
+    params: &ThresholdParameters,
+    context: &ElectionContext,
+    election_key: &ElectionKey,
+    ciphertexts: &[Ciphertext],
+    partials: &[PartialDecryption],
+) -> Result<Vec<RistrettoPoint>, ThresholdError> {
+    for (index, partial) in partials.iter().enumerate() {
+        if partials[..index].iter().any(|other| other.trustee == partial.trustee) {
+            return Err(ThresholdError::DuplicateTrustee { trustee: partial.trustee });
+        }
+        partial.verify(params, context, election_key, ciphertexts)?;
+    }
+
+    if partials.len() < params.threshold {
+        return Err(ThresholdError::NotEnoughShares { needed: params.threshold, actual: partials.len() });
+    }
+
+    let quorum: &[PartialDecryption] = &partials[..params.threshold];
+    let trustees: Vec<usize> = quorum.iter().map(|p| p.trustee).collect();
+    let coefficients: Vec<Scalar> = trustees.iter().map(|&trustee| lagrange_at_zero(trustee, &trustees)).collect();
+
+    Ok(ciphertexts
+        .iter()
+        .enumerate()
+        .map(|(index, ciphertext)| {
+            let mask: RistrettoPoint = quorum.iter().zip(&coefficients).map(|(p, l)| l * p.shares[index]).sum();
+            ciphertext.c2() - mask
+        })
+        .collect())
+}
+
+fn check_dealings(params: &ThresholdParameters, context: &ElectionContext, dealings: &[Dealing]) -> Result<(), ThresholdError> {
+    if dealings.len() != params.trustees {
+        return Err(ThresholdError::LengthMismatch { expected: params.trustees, actual: dealings.len() });
+    }
+    for (index, dealing) in dealings.iter().enumerate() {
+        if dealings[..index].iter().any(|other| other.dealer == dealing.dealer) {
+            return Err(ThresholdError::DuplicateTrustee { trustee: dealing.dealer });
+        }
+        dealing.verify(params, context)?;
+    }
+    Ok(())
+}
+
+/// Lagrange coefficient of `trustee` for interpolating at zero over `trustees`.
+fn lagrange_at_zero(trustee: usize, trustees: &[usize]) -> Scalar {
+    let x = Scalar::from(trustee as u64);
+    let (numerator, denominator) = trustees
+        .iter()
+        .filter(|&&other| other != trustee)
+        .map(|&other| Scalar::from(other as u64))
+        .fold((Scalar::one(), Scalar::one()), |(num, den), m| (num * m, den * (m - x)));
+    numerator * denominator.invert()
+}
+
+fn dealing_transcript(context: &ElectionContext, dealer: usize, commitments: &[RistrettoPoint]) -> Transcript {
+    let mut transcript = context.transcript(b"threshold key generation");
+    transcript.append_u64(b"dealer", dealer as u64);
+    for commitment in commitments {
+        transcript.append_message(b"coefficient commitment", commitment.compress().as_bytes());
+    }
+    transcript
+}
+
+fn decryption_transcript(context: &ElectionContext, trustee: usize) -> Transcript {
+    let mut transcript = context.transcript(b"threshold decryption");
+    transcript.append_u64(b"trustee", trustee as u64);
+    transcript
+}
+
+fn decryption_challenge(
+    transcript: &mut Transcript,
+    verification_key: &RistrettoPoint,
+    c1: &RistrettoPoint,
+    share: &RistrettoPoint,
+    nonce_commitments: &[CompressedRistretto; 2],
+) -> Scalar {
+    transcript.append_message(b"decryption verification key", verification_key.compress().as_bytes());
+    transcript.append_message(b"decryption c1", c1.compress().as_bytes());
+    transcript.append_message(b"decryption share", share.compress().as_bytes());
+    transcript.append_message(b"decryption nonce 0", nonce_commitments[0].as_bytes());
+    transcript.append_message(b"decryption nonce 1", nonce_commitments[1].as_bytes());
+    let mut challenge = [0u8; 64];
+    transcript.challenge_bytes(b"decryption challenge", &mut challenge);
+    Scalar::from_bytes_mod_order_wide(&challenge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+    use crate::ballot_validation::election_context::test_context;
+    use crate::ballot_validation::encrypted_ballot::{generate_encrypted_vote, EncryptedTally};
+    use crate::ballot_validation::rated_voting::max_budget::{self, MaxBudgetRatedVoting};
+
+    /// Runs the key generation among simulated trustees.
+    fn run_dkg(params: &ThresholdParameters) -> (ElectionKey, Vec<KeyShare>) {
+        let context = test_context();
+        let dealers: Vec<Dealer> = (1..=params.trustees())
+            .map(|dealer| Dealer::new(params, &context, dealer, &mut thread_rng()).unwrap())
+            .collect();
+        let dealings: Vec<Dealing> = dealers.iter().map(|d| d.dealing().clone()).collect();
+
+        let key_shares: Vec<KeyShare> = (1..=params.trustees())
+            .map(|trustee| {
+                let shares: Vec<Scalar> = dealers.iter().map(|d| d.share_for(trustee)).collect();
+                KeyShare::from_shares(params, &context, trustee, &dealings, &shares).unwrap()
+            })
+            .collect();
+
+        (ElectionKey::from_dealings(params, &context, &dealings).unwrap(), key_shares)
+    }
+
+    #[test]
+    fn test_threshold_decryption_of_tally() {
+        let pc_gens = PedersenGens::default();
+        let params = setup(3, 5, None).unwrap();
+        let (election_key, key_shares) = run_dkg(&params);
+
+        let ballot_params = max_budget::setup(10, None, None, 2, None).unwrap();
+        let mut tally = EncryptedTally::new(2);
+        for ballot in [[4, 6], [10, 0], [1, 1]] {
+            let ballot = generate_encrypted_vote::<MaxBudgetRatedVoting>(&ballot_params, election_key.public_key(), &test_context(), &ballot).unwrap();
+            tally.add(&ballot_params, election_key.public_key(), &test_context(), &ballot).unwrap();
+        }
+
+        let expected = vec![Scalar::from(15u64) * pc_gens.B, Scalar::from(7u64) * pc_gens.B];
+        for quorum in [[0, 1, 2], [4, 1, 3]] {
+            let partials: Vec<PartialDecryption> = quorum
+                .iter()
+                .map(|&i| key_shares[i].partial_decrypt(&params, &test_context(), tally.sums(), &mut thread_rng()))
+                .collect();
+            assert_eq!(combine_partial_decryptions(&params, &test_context(), &election_key, tally.sums(), &partials).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_not_enough_partial_decryptions() {
+        let params = setup(3, 5, None).unwrap();
+        let (election_key, key_shares) = run_dkg(&params);
+        let ciphertexts = [election_key.public_key().encrypt(&PedersenGens::default(), &Scalar::one(), &Scalar::random(&mut thread_rng()))];
+
+        let partials: Vec<PartialDecryption> = key_shares[..2]
+            .iter()
+            .map(|share| share.partial_decrypt(&params, &test_context(), &ciphertexts, &mut thread_rng()))
+            .collect();
+        assert_eq!(
+            combine_partial_decryptions(&params, &test_context(), &election_key, &ciphertexts, &partials).err(),
+            Some(ThresholdError::NotEnoughShares { needed: 3, actual: 2 })
+        );
+
+        let repeated = vec![partials[0].clone(), partials[1].clone(), partials[0].clone()];
+        assert_eq!(
+            combine_partial_decryptions(&params, &test_context(), &election_key, &ciphertexts, &repeated).err(),
+            Some(ThresholdError::DuplicateTrustee { trustee: 1 })
+        );
+    }
+
+    #[test]
+    fn test_forged_partial_decryption_is_rejected() {
+        let params = setup(2, 3, None).unwrap();
+        let (election_key, key_shares) = run_dkg(&params);
+        let ciphertexts = [election_key.public_key().encrypt(&PedersenGens::default(), &Scalar::one(), &Scalar::random(&mut thread_rng()))];
+
+        let mut forged = key_shares[1].partial_decrypt(&params, &test_context(), &ciphertexts, &mut thread_rng());
+        forged.shares[0] += PedersenGens::default().B;
+        assert_eq!(
+            forged.verify(&params, &test_context(), &election_key, &ciphertexts),
+            Err(ThresholdError::InvalidPartialDecryption { trustee: 2 })
+        );
+
+        // A valid partial decryption does not verify for another trustee.
+        let mut relabelled = key_shares[1].partial_decrypt(&params, &test_context(), &ciphertexts, &mut thread_rng());
+        relabelled.trustee = 3;
+        assert!(relabelled.verify(&params, &test_context(), &election_key, &ciphertexts).is_err());
+    }
+
+    #[test]
+    fn test_invalid_dealings_and_shares_are_detected() {
+        let params = setup(2, 3, None).unwrap();
+        let context = test_context();
+        let dealer = Dealer::new(&params, &context, 1, &mut thread_rng()).unwrap();
+        assert!(dealer.dealing().verify(&params, &context).is_ok());
+        assert!(dealer.dealing().verify_share(&params, 2, &dealer.share_for(2)).is_ok());
+
+        assert_eq!(dealer.dealing().verify_share(&params, 3, &dealer.share_for(2)), Err(ThresholdError::InvalidShare { dealer: 1 }));
+        assert_eq!(dealer.dealing().verify_share(&params, 4, &dealer.share_for(4)), Err(ThresholdError::UnknownTrustee { trustee: 4 }));
+
+        let mut tampered = dealer.dealing().clone();
+        tampered.commitments[0] += PedersenGens::default().B;
+        assert_eq!(tampered.verify(&params, &context), Err(ThresholdError::InvalidDealing { dealer: 1 }));
+
+        let other_context = ElectionContext::new(b"other election", b"test contest", b"test voter");
+        assert_eq!(dealer.dealing().verify(&params, &other_context), Err(ThresholdError::InvalidDealing { dealer: 1 }));
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert!(setup(0, 3, None).is_err());
+        assert!(setup(4, 3, None).is_err());
+        assert!(setup(3, 3, None).is_ok());
+
+        let params = setup(2, 3, None).unwrap();
+        assert_eq!(Dealer::new(&params, &test_context(), 0, &mut thread_rng()).err(), Some(ThresholdError::UnknownTrustee { trustee: 0 }));
+    }
+}
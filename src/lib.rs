@@ -1,7 +1,24 @@
 pub mod ballot_validation {
+    pub mod ballot_scheme;
+    pub mod discrete_log;
+    pub mod election_context;
+    pub mod elgamal;
+    pub mod encrypted_ballot;
+    pub mod encoding;
+    pub mod errors;
+    pub mod plurality;
     pub mod ranked_voting;
+    pub(crate) mod sigma;
+    pub mod tally;
+    pub mod threshold;
     pub mod rated_voting {
+        pub(crate) mod common;
+        pub mod approval;
+        pub mod exact_budget;
         pub mod max_budget;
+        pub mod multi_winner;
         pub mod no_budget;
+        #[cfg(feature = "r1cs")]
+        pub mod quadratic;
     }
-}
\ No newline at end of file
+}